@@ -0,0 +1,8 @@
+#![no_main]
+
+use cjs_module_lexer::utils::*;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    match_keyword(data);
+});
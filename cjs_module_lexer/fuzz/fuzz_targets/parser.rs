@@ -0,0 +1,31 @@
+#![no_main]
+
+use cjs_module_lexer::parser::*;
+use libfuzzer_sys::fuzz_target;
+use std::collections::HashMap;
+
+fuzz_target!(|data: &[u8]| {
+    let options = ParserOptions {
+        locations: true,
+        raw_names: true,
+        constants: HashMap::from([
+            (String::from("process.env.NODE_ENV"), String::from("production")),
+            (String::from("typeof window"), String::from("undefined")),
+        ]),
+        reconcile_exports: true,
+        ..Default::default()
+    };
+    let mut p = Parser::from_bytes_with_options(data, "fuzz.js", options);
+    p.parse();
+
+    let utf16: Vec<u16> = data
+        .chunks_exact(2)
+        .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+        .collect();
+    parse_utf16(&utf16, "fuzz.js", ParserOptions::default());
+
+    if let Ok(source) = std::str::from_utf8(data) {
+        let mut p = Parser::new(source, "fuzz.js");
+        p.parse();
+    }
+});
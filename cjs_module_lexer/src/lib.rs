@@ -1,2 +1,3 @@
+pub mod line_index;
 pub mod parser;
-pub mod utils;
\ No newline at end of file
+pub mod utils;
@@ -0,0 +1,161 @@
+use crate::parser::Span;
+
+/// Position of a byte offset as a line and a column.
+/// Lines and columns start at 0, like in the Language Server Protocol.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct LineCol {
+    pub line: usize,
+    /// Column in UTF-8 bytes
+    pub column: usize,
+    /// Column in Unicode scalar values, i.e. Rust `char`s
+    pub column_chars: usize,
+    /// Column in UTF-16 code units, like JavaScript string indices
+    pub column_utf16: usize,
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SpanLocation {
+    pub start: LineCol,
+    pub end: LineCol,
+}
+
+/// Converts byte offsets, like `ParseErrorMessage::pos` and `Span`, into lines and columns.
+///
+/// Line terminators are the ones of ECMAScript: `\n`, `\r\n`, `\r`, U+2028 and U+2029.
+pub struct LineIndex {
+    len: usize,
+    /// Byte offset where each line starts
+    line_starts: Vec<usize>,
+    /// Every char longer than 1 byte, and every invalid UTF-8 sequence, in source order
+    wide_chars: Vec<WideChar>,
+}
+
+/// The running totals make columns a binary search instead of a scan of the line,
+/// which matters for minified sources that are a single line.
+struct WideChar {
+    start: usize,
+    end: usize,
+    /// Bytes minus chars, from the start of the source up to `end`
+    extra_chars: usize,
+    /// Bytes minus UTF-16 code units, from the start of the source up to `end`
+    extra_utf16: usize,
+}
+
+impl LineIndex {
+    pub fn new(source: &str) -> LineIndex {
+        LineIndex::from_bytes(source.as_bytes())
+    }
+
+    /// For sources that are not valid UTF-8, like with `Parser::from_bytes`.
+    /// Each invalid sequence counts as one char, as if it were U+FFFD.
+    pub fn from_bytes(bytes: &[u8]) -> LineIndex {
+        let mut line_starts = vec![0];
+        let mut i = 0;
+
+        while i < bytes.len() {
+            match bytes[i] {
+                b'\r' if bytes.get(i + 1) == Some(&b'\n') => {
+                    i += 2;
+                    line_starts.push(i);
+                }
+                b'\n' | b'\r' => {
+                    i += 1;
+                    line_starts.push(i);
+                }
+                // U+2028 and U+2029 are E2 80 A8 and E2 80 A9
+                0xE2 if bytes.get(i + 1) == Some(&0x80)
+                    && matches!(bytes.get(i + 2), Some(0xA8 | 0xA9)) =>
+                {
+                    i += 3;
+                    line_starts.push(i);
+                }
+                _ => i += 1,
+            }
+        }
+
+        let mut wide_chars = vec![];
+        let mut extra_chars = 0;
+        let mut extra_utf16 = 0;
+        let mut pos = 0;
+        let mut add_wide_char = |start: usize, len: usize, len_utf16: usize| {
+            extra_chars += len - 1;
+            extra_utf16 += len - len_utf16;
+            wide_chars.push(WideChar {
+                start,
+                end: start + len,
+                extra_chars,
+                extra_utf16,
+            });
+        };
+
+        for chunk in bytes.utf8_chunks() {
+            for ch in chunk.valid().chars() {
+                if ch.len_utf8() > 1 {
+                    add_wide_char(pos, ch.len_utf8(), ch.len_utf16());
+                }
+                pos += ch.len_utf8();
+            }
+            if !chunk.invalid().is_empty() {
+                add_wide_char(pos, chunk.invalid().len(), 1);
+                pos += chunk.invalid().len();
+            }
+        }
+
+        LineIndex {
+            len: bytes.len(),
+            line_starts,
+            wide_chars,
+        }
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// Offsets past the end are clamped to the end, and offsets inside a character
+    /// are moved back to the start of that character.
+    pub fn line_col(&self, offset: usize) -> LineCol {
+        let mut offset = offset.min(self.len);
+        let before = self.wide_chars.partition_point(|ch| ch.end <= offset);
+        if let Some(ch) = self.wide_chars.get(before) {
+            if ch.start < offset {
+                offset = ch.start;
+            }
+        }
+
+        let line = match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(next_line) => next_line - 1,
+        };
+        let line_start = self.line_starts[line];
+        let column = offset - line_start;
+
+        let (extra_chars, extra_utf16) = self.extra_before(offset);
+        let (line_extra_chars, line_extra_utf16) = self.extra_before(line_start);
+
+        LineCol {
+            line,
+            column,
+            column_chars: column - (extra_chars - line_extra_chars),
+            column_utf16: column - (extra_utf16 - line_extra_utf16),
+        }
+    }
+
+    /// Running totals of the wide chars ending at or before `offset`
+    fn extra_before(&self, offset: usize) -> (usize, usize) {
+        match self.wide_chars.partition_point(|ch| ch.end <= offset) {
+            0 => (0, 0),
+            before => {
+                let ch = &self.wide_chars[before - 1];
+                (ch.extra_chars, ch.extra_utf16)
+            }
+        }
+    }
+
+    pub fn span_location(&self, span: Span) -> SpanLocation {
+        SpanLocation {
+            start: self.line_col(span.start),
+            end: self.line_col(span.end),
+        }
+    }
+}
@@ -1,13 +1,158 @@
+use crate::line_index::{LineCol, LineIndex, SpanLocation};
 use crate::utils::*;
+use std::collections::{HashMap, HashSet};
 
-#[derive(Clone, Debug, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+#[derive(Clone, Debug, Default, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct ParseResult {
     pub imports: Vec<String>,
     pub exports: Vec<String>,
     pub reexports: Vec<String>,
     pub errors: Vec<ParseError>,
+    /// Same order as `imports`, with the interop helper each `require` is wrapped in
+    pub import_records: Vec<ImportRecord>,
+    /// Whether `__esModule` is set, by `exports.__esModule = true` or
+    /// `Object.defineProperty(exports, '__esModule', { value: true })`
+    pub es_module: bool,
+    /// `import('x')` with a string literal or a template literal without substitutions
+    pub dynamic_imports: Vec<DynamicImport>,
+    /// Same order as `exports`, with the source location of each export
+    pub export_records: Vec<ExportRecord>,
+    /// Same order as `reexports`, with the source location of each reexport
+    pub reexport_records: Vec<ReexportRecord>,
+    /// Every `module.exports = require('x')`, of which only the last one is in `reexports`,
+    /// like `if (prod) module.exports = require('./prod'); else module.exports = require('./dev');`
+    pub reexport_candidates: Vec<ReexportCandidate>,
+    /// Lines and columns of the errors and spans above, with `ParserOptions::locations`
+    pub locations: Option<Locations>,
+}
+
+/// Each field has the same order as the field of the same name in `ParseResult`
+#[derive(Clone, Debug, Default, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Locations {
+    pub errors: Vec<LineCol>,
+    pub import_records: Vec<RecordLocation>,
+    pub export_records: Vec<RecordLocation>,
+    pub reexport_records: Vec<RecordLocation>,
+    pub reexport_candidates: Vec<RecordLocation>,
+    pub dynamic_imports: Vec<SpanLocation>,
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct RecordLocation {
+    pub span: SpanLocation,
+    pub statement_span: SpanLocation,
+}
+
+/// Byte offsets into the source, `end` being exclusive
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct DynamicImport {
+    pub specifier: String,
+    /// The specifier literal, quotes included
+    pub span: Span,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ParserOptions {
+    /// Also report `__esModule` in `ParseResult::exports`, like upstream cjs-module-lexer does.
+    /// `ParseResult::es_module` is set either way.
+    pub es_module_export: bool,
+    /// Fill `ParseResult::locations`
+    pub locations: bool,
+    /// Fill `ExportRecord::raw_name`, for sources that are not valid UTF-8
+    pub raw_names: bool,
+    /// Values of constant expressions, written like `process.env.NODE_ENV` or `typeof window`.
+    /// For `if (process.env.NODE_ENV === 'production') ... else ...`, where one side of `===`,
+    /// `==`, `!==` or `!=` is one of them and the other a string, only the taken branch is recorded.
+    pub constants: HashMap<String, String>,
+    /// Only report what `module.exports` ends up with, like Node: a top level
+    /// `module.exports = ...` drops the exports and reexports before it, `delete exports.a`
+    /// drops `a`, and each name is reported once. By default exports are only appended, like upstream.
+    pub reconcile_exports: bool,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ImportRecord {
+    pub specifier: String,
+    pub interop: Interop,
+    /// The specifier literal, quotes included
+    pub span: Span,
+    /// The `require('x')` call
+    pub statement_span: Span,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ExportRecord {
+    pub name: String,
+    /// The identifier or string literal naming the export, quotes included
+    pub span: Span,
+    /// What the export was detected from, as far as the lexer reads it, like `exports.a =`,
+    /// `module.exports = { a }` or `Object.defineProperty(exports, 'a', { ... })`
+    pub statement_span: Span,
+    /// `name` before it is lossily decoded as UTF-8, with `ParserOptions::raw_names`
+    pub raw_name: Option<Vec<u8>>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ReexportRecord {
+    pub specifier: String,
+    /// The specifier literal inside `require(...)`, quotes included
+    pub span: Span,
+    /// What the reexport was detected from, as far as the lexer reads it, like
+    /// `module.exports = require('x')` or `__exportStar(require('x')`
+    pub statement_span: Span,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ReexportCandidate {
+    pub record: ReexportRecord,
+    /// Whether the statement is at top level, rather than inside brackets, like a block or a
+    /// function, or after `if (...)`, `else`, `?`, `:`, `&&`, `||` or `??`
+    pub top_level: bool,
 }
 
+/// How the value of a `require('x')` is consumed
+#[derive(Clone, Copy, Debug, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum Interop {
+    /// `require('x')`
+    Plain,
+    /// `_interopRequireDefault(require('x'))`, `__importDefault(require('x'))`
+    Default,
+    /// `_interopRequireWildcard(require('x'))`, `__importStar(require('x'))`
+    Namespace,
+}
+
+const MAX_BRANCH_DEPTH: usize = 64;
+
+/// What `parse_branch` sets aside while it scans an untaken branch
+struct BranchState {
+    parse_result: ParseResult,
+    export_aliases: HashSet<String>,
+    require_bindings: HashMap<String, ImportRecord>,
+    esbuild_exports: HashMap<String, Vec<ExportRecord>>,
+}
+
+enum ConstantOperand {
+    String(String),
+    /// `process.env.NODE_ENV`, `typeof window`
+    Expression(String),
+}
+
+/// Babel, SWC and TypeScript helpers wrapping `require`
+static INTEROP_HELPERS: &[(&[u8], Interop)] = &[
+    (b"_interopRequireDefault", Interop::Default),
+    (b"_interopRequireWildcard", Interop::Namespace),
+    (b"_interop_require_default", Interop::Default),
+    (b"_interop_require_wildcard", Interop::Namespace),
+    (b"__importDefault", Interop::Default),
+    (b"__importStar", Interop::Namespace),
+];
+
 #[derive(Clone, Debug, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct ParseErrorMessage {
     pub pos: usize,
@@ -22,6 +167,176 @@ pub enum ParseError {
     UnexpectedBracket(char, ParseErrorMessage),
     IncorrectClosingBracket(char, ParseErrorMessage),
     UnterminatedRegExp(ParseErrorMessage),
+    /// `import x from 'x'` at top level, which means the source is an ES module
+    UnexpectedImportStatement(ParseErrorMessage),
+    /// `export const x = 1` at top level, which means the source is an ES module
+    UnexpectedExportStatement(ParseErrorMessage),
+    /// `import.meta`, which means the source is an ES module
+    UnexpectedImportMeta(ParseErrorMessage),
+    /// `'\uD800'`, a surrogate not part of a pair, with its code unit
+    LoneSurrogate(u32, ParseErrorMessage),
+    /// Bytes that are not valid UTF-8, with `Parser::from_bytes`
+    InvalidUtf8(ParseErrorMessage),
+}
+
+impl ParseError {
+    fn message_mut(&mut self) -> &mut ParseErrorMessage {
+        match self {
+            ParseError::UnexpectedEOF(message)
+            | ParseError::UnexpectedEscapeCharacter(_, message)
+            | ParseError::UnexpectedUnicodeEscapeSequence(_, message)
+            | ParseError::UnexpectedBracket(_, message)
+            | ParseError::IncorrectClosingBracket(_, message)
+            | ParseError::UnterminatedRegExp(message)
+            | ParseError::UnexpectedImportStatement(message)
+            | ParseError::UnexpectedExportStatement(message)
+            | ParseError::UnexpectedImportMeta(message)
+            | ParseError::LoneSurrogate(_, message)
+            | ParseError::InvalidUtf8(message) => message,
+        }
+    }
+
+    pub fn message(&self) -> &ParseErrorMessage {
+        match self {
+            ParseError::UnexpectedEOF(message)
+            | ParseError::UnexpectedEscapeCharacter(_, message)
+            | ParseError::UnexpectedUnicodeEscapeSequence(_, message)
+            | ParseError::UnexpectedBracket(_, message)
+            | ParseError::IncorrectClosingBracket(_, message)
+            | ParseError::UnterminatedRegExp(message)
+            | ParseError::UnexpectedImportStatement(message)
+            | ParseError::UnexpectedExportStatement(message)
+            | ParseError::UnexpectedImportMeta(message)
+            | ParseError::LoneSurrogate(_, message)
+            | ParseError::InvalidUtf8(message) => message,
+        }
+    }
+}
+
+impl ParseResult {
+    /// Applies `f` to every offset, which are in the errors and spans
+    fn map_offsets(&mut self, f: impl Fn(usize) -> usize) {
+        let map_span = |span: &mut Span| {
+            span.start = f(span.start);
+            span.end = f(span.end);
+        };
+
+        for error in &mut self.errors {
+            let message = error.message_mut();
+            message.pos = f(message.pos);
+        }
+        for record in &mut self.import_records {
+            map_span(&mut record.span);
+            map_span(&mut record.statement_span);
+        }
+        for record in &mut self.export_records {
+            map_span(&mut record.span);
+            map_span(&mut record.statement_span);
+        }
+        for record in &mut self.reexport_records {
+            map_span(&mut record.span);
+            map_span(&mut record.statement_span);
+        }
+        for candidate in &mut self.reexport_candidates {
+            map_span(&mut candidate.record.span);
+            map_span(&mut candidate.record.statement_span);
+        }
+        for dynamic_import in &mut self.dynamic_imports {
+            map_span(&mut dynamic_import.span);
+        }
+    }
+
+    /// Lines and columns of the errors and spans, `index` being built from the parsed source
+    pub fn compute_locations(&self, index: &LineIndex) -> Locations {
+        let record = |span: Span, statement_span: Span| RecordLocation {
+            span: index.span_location(span),
+            statement_span: index.span_location(statement_span),
+        };
+
+        Locations {
+            errors: self
+                .errors
+                .iter()
+                .map(|error| index.line_col(error.message().pos))
+                .collect(),
+            import_records: self
+                .import_records
+                .iter()
+                .map(|r| record(r.span, r.statement_span))
+                .collect(),
+            export_records: self
+                .export_records
+                .iter()
+                .map(|r| record(r.span, r.statement_span))
+                .collect(),
+            reexport_records: self
+                .reexport_records
+                .iter()
+                .map(|r| record(r.span, r.statement_span))
+                .collect(),
+            reexport_candidates: self
+                .reexport_candidates
+                .iter()
+                .map(|c| record(c.record.span, c.record.statement_span))
+                .collect(),
+            dynamic_imports: self
+                .dynamic_imports
+                .iter()
+                .map(|r| index.span_location(r.span))
+                .collect(),
+        }
+    }
+
+    /// Whether ESM syntax was found, so the source is not actually CommonJS
+    pub fn is_esm(&self) -> bool {
+        self.errors.iter().any(|error| {
+            matches!(
+                error,
+                ParseError::UnexpectedImportStatement(_)
+                    | ParseError::UnexpectedExportStatement(_)
+                    | ParseError::UnexpectedImportMeta(_)
+            )
+        })
+    }
+}
+
+/// Parses UTF-16 source, like a JS string, which may contain lone surrogates.
+///
+/// The source is converted to UTF-8, lone surrogates becoming U+FFFD, and offsets in the result,
+/// like `Span` and `ParseErrorMessage::pos`, are converted back to UTF-16 code units.
+/// `ParseResult::locations` is computed on the UTF-8 source, which only matters for `LineCol::column`.
+pub fn parse_utf16(source: &[u16], filename: &str, options: ParserOptions) -> ParseResult {
+    let mut utf8 = Vec::with_capacity(source.len());
+    // UTF-8 offset after each non-ASCII char, and how many more bytes than UTF-16 code units
+    // there are up to it
+    let mut wide_chars: Vec<(usize, usize)> = vec![];
+    let mut extra = 0;
+
+    for ch in char::decode_utf16(source.iter().copied()) {
+        let (ch, len_utf16) = match ch {
+            Ok(ch) => (ch, ch.len_utf16()),
+            Err(_) => (char::REPLACEMENT_CHARACTER, 1),
+        };
+        let mut buf = [0; 4];
+        utf8.extend_from_slice(ch.encode_utf8(&mut buf).as_bytes());
+
+        if ch.len_utf8() != len_utf16 {
+            extra += ch.len_utf8() - len_utf16;
+            wide_chars.push((utf8.len(), extra));
+        }
+    }
+
+    let mut p = Parser::from_bytes_with_options(&utf8, filename, options);
+    p.check_utf8 = false;
+    let mut result = p.parse();
+
+    result.map_offsets(
+        |offset| match wide_chars.partition_point(|(end, _)| *end <= offset) {
+            0 => offset,
+            before => offset - wide_chars[before - 1].1,
+        },
+    );
+    result
 }
 
 pub struct Parser<'a> {
@@ -30,27 +345,93 @@ pub struct Parser<'a> {
     pub filename: &'a str,
     pub open_token_depth: usize,
     pub parse_result: ParseResult,
+    pub options: ParserOptions,
     bracket_stack: Vec<Bracket>,
-    parenthesis_type: ParenthesisType,
-    expect_expression: bool,
+    /// Last char of the last token, to tell a regex from a division like lexer.c
+    last_token_pos: Option<usize>,
+    /// The last bracket closed by the main loop, with the position of its closing char.
+    /// Brackets closed by the `try_parse_*` helpers are calls and object literals.
+    last_closed_bracket: Option<(usize, Bracket)>,
+    last_slash_was_division: bool,
+    next_brace_is_class: bool,
+    /// Where the last `comment_whitespace` started and ended, so that the end of the token
+    /// before it is known
+    whitespace_start: usize,
+    whitespace_end: usize,
+    /// Values of `module.exports = { ... }` are scanned by the main loop, where another
+    /// `module.exports = { ... }` is not read as exports
+    in_literal_exports: bool,
+    /// How many `parse_branch` calls are nested, which recurse through the main loop
+    branch_depth: usize,
+    /// `e` for every top level `var e = module.exports`, `const e = exports`
+    /// or `module.exports = e = { ... }`
+    export_aliases: HashSet<String>,
+    /// Whether `exports` is the previous exports object, after `module.exports = x` with
+    /// `ParserOptions::reconcile_exports`, until `module.exports = exports = x`
+    exports_stale: bool,
+    /// `_x` → `require('x')` for every `var _x = require('x')`
+    require_bindings: HashMap<String, ImportRecord>,
+    /// `src_exports` → `a` for every esbuild `__export(src_exports, { a: () => a })`
+    esbuild_exports: HashMap<String, Vec<ExportRecord>>,
+    /// Index in `parse_result.errors` of each error of `add_escape_error`
+    escape_errors: HashMap<(usize, std::mem::Discriminant<ParseError>), usize>,
+    /// Whether `source` may not be valid UTF-8
+    check_utf8: bool,
+    /// Where the source starts after the UTF-8 BOM, if any
+    start_pos: usize,
 }
 
 impl<'a> Parser<'a> {
     pub fn new(source_str: &'a str, filename: &'a str) -> Parser<'a> {
+        Parser::with_options(source_str, filename, ParserOptions::default())
+    }
+
+    pub fn with_options(
+        source_str: &'a str,
+        filename: &'a str,
+        options: ParserOptions,
+    ) -> Parser<'a> {
+        Parser {
+            check_utf8: false,
+            ..Parser::from_bytes_with_options(source_str.as_bytes(), filename, options)
+        }
+    }
+
+    /// Like `Parser::new`, but `source` does not have to be valid UTF-8.
+    /// Invalid sequences are reported as `ParseError::InvalidUtf8`, and names containing them
+    /// are decoded lossily, see `ParserOptions::raw_names`.
+    pub fn from_bytes(source: &'a [u8], filename: &'a str) -> Parser<'a> {
+        Parser::from_bytes_with_options(source, filename, ParserOptions::default())
+    }
+
+    pub fn from_bytes_with_options(
+        source: &'a [u8],
+        filename: &'a str,
+        options: ParserOptions,
+    ) -> Parser<'a> {
         Parser {
-            source: source_str.as_bytes(),
+            source,
             pos: 0,
             filename,
             open_token_depth: 0,
-            parse_result: ParseResult {
-                imports: vec![],
-                exports: vec![],
-                reexports: vec![],
-                errors: vec![],
-            },
+            parse_result: ParseResult::default(),
+            options,
             bracket_stack: std::vec::Vec::with_capacity(8),
-            parenthesis_type: ParenthesisType::Plain,
-            expect_expression: true,
+            last_token_pos: None,
+            last_closed_bracket: None,
+            last_slash_was_division: false,
+            next_brace_is_class: false,
+            whitespace_start: 0,
+            whitespace_end: 0,
+            in_literal_exports: false,
+            branch_depth: 0,
+            export_aliases: HashSet::new(),
+            exports_stale: false,
+            require_bindings: HashMap::new(),
+            esbuild_exports: HashMap::new(),
+            escape_errors: HashMap::new(),
+            check_utf8: true,
+            start_pos: 0,
         }
     }
 
@@ -69,7 +450,8 @@ impl<'a> Parser<'a> {
     fn next_offset(&mut self, offset: usize) {
         // print!("stack = {} ", self.bracket_stack.len());
         // self.print_current_line();
-        self.pos += offset;
+        // Never past the end, so that `self.source[self.pos..]` is always valid
+        self.pos = std::cmp::min(self.pos + offset, self.source.len());
     }
 
     fn cur_offset(&self, offset: usize) -> Option<u8> {
@@ -77,23 +459,46 @@ impl<'a> Parser<'a> {
     }
 
     fn cur_neg_offset(&self, offset: usize) -> Option<u8> {
-        if self.pos >= offset {
+        if self.pos >= self.start_pos + offset {
             self.source.get(self.pos - offset).map(|c| *c)
         } else {
             None
         }
     }
 
+    /// The char starting at `self.pos`, `None` if `self.pos` is not at the start of a valid char
     fn full_char_code(&self) -> Option<char> {
-        unsafe {
-            std::str::from_utf8_unchecked(&self.source[self.pos..])
-                .chars()
-                .next()
+        let end = std::cmp::min(self.pos + 4, self.source.len());
+        let bytes = self.source.get(self.pos..end)?;
+        let valid = match std::str::from_utf8(bytes) {
+            Ok(valid) => valid,
+            Err(error) => std::str::from_utf8(&bytes[..error.valid_up_to()]).ok()?,
+        };
+        valid.chars().next()
+    }
+
+    /// Length of the invalid UTF-8 sequence at `self.pos`, if any
+    fn invalid_utf8_len(&self) -> Option<usize> {
+        if !self.check_utf8 || self.cur()? < 0x80 {
+            return None;
+        }
+
+        let end = std::cmp::min(self.pos + 4, self.source.len());
+        let bytes = &self.source[self.pos..end];
+        match std::str::from_utf8(bytes) {
+            Err(error) if error.valid_up_to() == 0 => {
+                Some(error.error_len().unwrap_or(bytes.len()))
+            }
+            _ => None,
         }
     }
 
     fn line_comment(&mut self) {
         while let Some(c) = self.cur() {
+            if let Some(len) = non_ascii_br_len(&self.source[self.pos..]) {
+                self.next_offset(len);
+                return;
+            }
             self.next();
             if is_br(c) {
                 return;
@@ -115,52 +520,159 @@ impl<'a> Parser<'a> {
     }
 
     // Reference: https://tc39.es/ecma262/#table-white-space-code-points
-    // Non-ascii whitespaces are omitted
     fn comment_whitespace(&mut self) {
+        self.whitespace_start = self.pos;
         while let Some(c) = self.cur() {
             match c {
                 b'\x09' | b'\x0b' | b'\x0c' | b' ' | b'\r' | b'\n' => {
                     self.next();
                     continue;
                 }
+                0x80.. => match non_ascii_ws_len(&self.source[self.pos..]) {
+                    Some(len) => self.next_offset(len),
+                    None => break,
+                },
                 b'/' if self.cur_offset(1) == Some(b'/') => self.line_comment(),
                 b'/' if self.cur_offset(1) == Some(b'*') => self.block_comment(),
                 _ => break,
             }
         }
+        self.whitespace_end = self.pos;
     }
 
     fn identifer(&mut self) -> Option<String> {
         // lexer.c identifier
         let mut result = String::with_capacity(16);
 
-        if let Some(ch) = self.full_char_code() {
-            if !is_identifier_start(ch) && ch != '\\' {
-                return None;
+        let ch = self.identifier_part(true)?;
+        result.push(ch);
+
+        while self.cur().is_some() {
+            match self.identifier_part(false) {
+                Some(ch) => result.push(ch),
+                None if self.cur() == Some(b'\\') => {
+                    // The escape is not an identifier char, so neither is the whole identifier
+                    return None;
+                }
+                None => return Some(result),
             }
+        }
+
+        Some(result)
+    }
 
-            result.push(ch);
+    /// Consumes one char of an identifier, which may be escaped like `\u0061` or `\u{61}`.
+    /// An escape that is not an identifier char is reported, leaving `self.pos` at the `\`.
+    fn identifier_part(&mut self, start: bool) -> Option<char> {
+        let is_valid = if start {
+            is_identifier_start
+        } else {
+            is_identifier_char
+        };
+
+        if self.cur() != Some(b'\\') {
+            if let Some(len) = self.invalid_utf8_len() {
+                // Likely a letter in another encoding, like Latin-1 `é`
+                self.next_offset(len);
+                return Some(char::REPLACEMENT_CHARACTER);
+            }
+            let ch = self.full_char_code()?;
+            if !is_valid(ch) {
+                return None;
+            }
             self.next_offset(ch.len_utf8());
+            return Some(ch);
+        }
 
-            while let Some(ch) = self.full_char_code() {
-                if is_identifier_char(ch) {
-                    result.push(ch);
-                    self.next_offset(ch.len_utf8());
-                } else {
-                    // no identifier escapes support for now
-                    self.expect_expression = false;
-                    return Some(result);
+        let escape_pos = self.pos;
+        self.next();
+        let is_unicode_escape = self.cur() == Some(b'u');
+        let ch = match self.unicode_escape_code_point().map(char::from_u32) {
+            Some(Some(ch)) if is_valid(ch) => ch,
+            decoded => {
+                // Malformed `\u` escapes are already reported
+                if decoded.is_some() || !is_unicode_escape {
+                    self.add_escape_error(ParseError::UnexpectedUnicodeEscapeSequence(
+                        decoded.flatten().unwrap_or('\\'),
+                        ParseErrorMessage {
+                            pos: escape_pos,
+                            message: String::from("Invalid escape in identifier. Expected a unicode escape sequence of an identifier char. "),
+                        },
+                    ));
                 }
+                self.pos = escape_pos;
+                return None;
             }
+        };
 
-            return Some(result);
-        } else {
-            return None;
+        Some(ch)
+    }
+
+    /// Escapes are read again when a try-parse function reverts, so each is reported once,
+    /// unless the errors were truncated since.
+    fn add_escape_error(&mut self, error: ParseError) {
+        let key = (error.message().pos, std::mem::discriminant(&error));
+        let errors = &mut self.parse_result.errors;
+
+        if let Some(&index) = self.escape_errors.get(&key) {
+            if errors.get(index) == Some(&error) {
+                return;
+            }
         }
+        self.escape_errors.insert(key, errors.len());
+        errors.push(error);
     }
 
     /// https://tc39.es/ecma262/#prod-UnicodeEscapeSequence
-    fn unicode_escape_sequence(&mut self) -> Option<Vec<u8>> {
+    ///
+    /// `\uD83D\uDE00` is combined into one code point like in JS. A lone surrogate is decoded as
+    /// U+FFFD, and reported as `ParseError::LoneSurrogate` unless the value is skipped anyway.
+    fn unicode_escape_sequence(&mut self, skip: bool) -> Option<Vec<u8>> {
+        let escape_pos = self.pos - 1;
+        let mut code_point = self.unicode_escape_code_point()?;
+
+        if (0xD800..=0xDBFF).contains(&code_point)
+            && self.cur() == Some(b'\\')
+            && self.cur_offset(1) == Some(b'u')
+        {
+            let revert_pos = self.pos;
+            let errors = self.parse_result.errors.len();
+            self.next();
+
+            match self.unicode_escape_code_point() {
+                Some(low @ 0xDC00..=0xDFFF) => {
+                    code_point = 0x10000 + ((code_point - 0xD800) << 10) + (low - 0xDC00);
+                }
+                _ => {
+                    // The next escape is read again on its own
+                    self.parse_result.errors.truncate(errors);
+                    self.pos = revert_pos;
+                }
+            }
+        }
+
+        let ch = match char::from_u32(code_point) {
+            Some(ch) => ch,
+            None => {
+                if !skip {
+                    self.add_escape_error(ParseError::LoneSurrogate(
+                        code_point,
+                        ParseErrorMessage {
+                            pos: escape_pos,
+                            message: String::from("Lone surrogate in unicode escape sequence is replaced with U+FFFD. "),
+                        },
+                    ));
+                }
+                char::REPLACEMENT_CHARACTER
+            }
+        };
+
+        let mut buf = [0; 4];
+        Some(ch.encode_utf8(&mut buf).as_bytes().to_vec())
+    }
+
+    /// The code point of `uXXXX` or `u{X...}`, following a `\\`
+    fn unicode_escape_code_point(&mut self) -> Option<u32> {
         if let Some(b'u') = self.cur() {
             self.next();
 
@@ -171,7 +683,15 @@ impl<'a> Parser<'a> {
                 digit_string = self.hex_digits()?;
 
                 if self.cur() != Some(b'}') {
-                    // TODO: report error
+                    if let Some(c) = self.full_char_code() {
+                        self.add_escape_error(ParseError::UnexpectedUnicodeEscapeSequence(
+                            c,
+                            ParseErrorMessage {
+                                pos: self.pos,
+                                message: String::from("Unexpected character is found in unicode escaped sequence. Expected a hex character or `}`. "),
+                            },
+                        ));
+                    }
                     return None;
                 } else {
                     self.next();
@@ -187,17 +707,33 @@ impl<'a> Parser<'a> {
                 .collect();
             }
 
-            let code_point =
-                char::from_u32(u32::from_str_radix(digit_string.as_str(), 16).unwrap()).unwrap();
+            // `\u{0000000061}` is fine, only the value is limited
+            let digit_string = match digit_string.trim_start_matches('0') {
+                "" => "0",
+                digits => digits,
+            };
 
-            let encoded: String = [code_point].into_iter().collect();
-            return Some(encoded.into_bytes());
+            return match u32::from_str_radix(digit_string, 16) {
+                Ok(code_point) if code_point <= 0x10FFFF => Some(code_point),
+                _ => {
+                    self.add_escape_error(ParseError::UnexpectedUnicodeEscapeSequence(
+                        '}',
+                        ParseErrorMessage {
+                            pos: self.pos - 1,
+                            message: String::from(
+                                "Code point in unicode escape sequence is greater than 0x10FFFF. ",
+                            ),
+                        },
+                    ));
+                    None
+                }
+            };
         }
         return None;
     }
 
     fn hex_digits(&mut self) -> Option<String> {
-        if let Some(first) = self.hex_digit(false) {
+        if let Some(first) = self.hex_digit(true) {
             let mut result = String::with_capacity(16);
             result.push(first);
 
@@ -219,9 +755,7 @@ impl<'a> Parser<'a> {
             }
             Some(c) => {
                 if required {
-                    self.parse_result
-                    .errors
-                    .push(ParseError::UnexpectedUnicodeEscapeSequence(
+                    self.add_escape_error(ParseError::UnexpectedUnicodeEscapeSequence(
                         char::from(c),
                         ParseErrorMessage {
                             pos: self.pos,
@@ -236,7 +770,8 @@ impl<'a> Parser<'a> {
     }
 
     // @see https://tc39.es/ecma262/#prod-EscapeSequence
-    fn string_escape_sequence(&mut self) -> Option<Vec<u8>> {
+    /// `skip` is whether the value is going to be thrown away.
+    fn string_escape_sequence(&mut self, skip: bool) -> Option<Vec<u8>> {
         if let Some(b'\\') = self.cur() {
             self.next();
 
@@ -281,39 +816,34 @@ impl<'a> Parser<'a> {
                     }
                     return Some(vec![b'\r']);
                 }
-                Some(b'0') => {
-                    if let Some(b'0'..=b'9') = self.cur_offset(1) {
-                        self.parse_result
-                            .errors
-                            .push(ParseError::UnexpectedEscapeCharacter(
-                                '0',
-                                ParseErrorMessage {
-                                    pos: self.pos,
-                                    message: format!(
-                                        "Sorry, LegacyOctalEscapeSequence is not supported. "
-                                    ),
-                                },
-                            ));
-                        return None;
-                    } else {
-                        // We got a \0
-
-                        self.parse_result
-                            .errors
-                            .push(ParseError::UnexpectedEscapeCharacter(
-                                '0',
-                                ParseErrorMessage {
-                                    pos: self.pos,
-                                    message: format!("Sorry, \0 in string literal is not supported. Inputs like `exports['\\0'] = 1` will be returned with the escape sequence as-is. "),
-                                },
-                            ));
-                        self.next();
-
-                        return Some(vec![b'\\', b'\0']);
+                // https://tc39.es/ecma262/#prod-annexB-LegacyOctalEscapeSequence
+                // `\0` is included, it is just not legacy
+                Some(first @ b'0'..=b'7') => {
+                    self.next();
+                    // Up to `\377`, which is 255
+                    let max_len = if first <= b'3' { 3 } else { 2 };
+                    let mut value = first - b'0';
+
+                    for _ in 1..max_len {
+                        match self.cur() {
+                            Some(c @ b'0'..=b'7') => {
+                                self.next();
+                                value = value * 8 + (c - b'0');
+                            }
+                            _ => break,
+                        }
                     }
+
+                    let mut buf = [0; 4];
+                    Some(char::from(value).encode_utf8(&mut buf).as_bytes().to_vec())
+                }
+                // https://tc39.es/ecma262/#prod-annexB-NonOctalDecimalEscapeSequence
+                Some(c @ (b'8' | b'9')) => {
+                    self.next();
+                    Some(vec![c])
                 }
                 Some(b'u') => {
-                    return self.unicode_escape_sequence();
+                    return self.unicode_escape_sequence(skip);
                 }
                 Some(c) => {
                     self.next();
@@ -327,6 +857,12 @@ impl<'a> Parser<'a> {
     }
 
     fn string_literal(&mut self, skip: bool) -> Option<String> {
+        let bytes = self.string_literal_bytes(skip)?;
+        Some(String::from_utf8_lossy(&bytes).into_owned())
+    }
+
+    /// The value of a string literal, which is not valid UTF-8 if the source is not
+    fn string_literal_bytes(&mut self, skip: bool) -> Option<Vec<u8>> {
         // lexer.c stringLiteral
         match self.cur() {
             Some(quote @ (b'\'' | b'"')) => {
@@ -342,14 +878,10 @@ impl<'a> Parser<'a> {
                     match c {
                         c if c == quote => {
                             self.next();
-                            self.expect_expression = false;
-                            if skip {
-                                return Some(String::from(""));
-                            }
-                            return Some(String::from_utf8(result.unwrap()).unwrap());
+                            return Some(result.unwrap_or_default());
                         }
                         b'\\' => {
-                            if let Some(escaped) = self.string_escape_sequence() {
+                            if let Some(escaped) = self.string_escape_sequence(skip) {
                                 if !skip {
                                     result.as_mut().unwrap().extend(escaped.iter());
                                 }
@@ -385,67 +917,243 @@ impl<'a> Parser<'a> {
                     result.push(char::from(c));
                     self.next();
                 }
-                _ => {
-                    self.expect_expression = false;
-                    return Some(result);
-                }
+                _ => return Some(result),
             }
         }
         None
     }
 
-    fn try_parse_literal_exports(&mut self) {
+    /// `statement_start` is where `module.exports = {` starts.
+    /// Returns whether the closing `}` is reached.
+    fn try_parse_literal_exports(&mut self, statement_start: usize) -> bool {
         // lexer.c tryParseLiteralExports
-        let revert_pos = self.pos - 1;
         if self.cur() != Some(b'{') {
             // TODO: report error
-            return;
+            return false;
         }
 
+        // The brace stays open when the object cannot be read to the end, so that the main loop
+        // continues with balanced brackets.
+        self.bracket_stack.push(Bracket::Brace(BraceType::Plain));
+        self.last_token_pos = Some(self.pos);
         self.next();
+        self.in_literal_exports = true;
 
-        while let Some(_) = self.cur() {
-            self.comment_whitespace();
-
-            if let Some(identifier) = self.identifer() {
-                self.comment_whitespace();
+        // The statement ends at `}`, so exports are only added once it is reached
+        let mut names: Vec<(String, Span)> = vec![];
+        let mut closed = false;
 
-                if self.cur() == Some(b':') {
-                    // { a: a }
+        while self.try_parse_literal_property(&mut names) {
+            match self.cur() {
+                Some(b',') => {
+                    self.last_token_pos = Some(self.pos);
                     self.next();
-                    self.comment_whitespace();
+                }
+                _ => {
+                    // `}`
+                    self.pop_bracket_stack();
+                    closed = true;
+                    break;
+                }
+            }
+        }
+
+        self.in_literal_exports = false;
+        self.add_literal_exports(names, statement_start);
+        closed
+    }
 
-                    if let Some(_) = self.identifer() {
-                    } else if let Some(_) = self.string_literal(true) {
-                    } else if let Some(_) = self.number_literal() {
+    /// One property of `module.exports = { ... }`, like `a`, `a: x`, `'a': x`, `1: x`, `a() {}`,
+    /// `get a() {}`, `async *a() {}`, `[a]: x` or `...require('a')`.
+    /// Returns whether the `,` or `}` after it is reached.
+    fn try_parse_literal_property(&mut self, names: &mut Vec<(String, Span)>) -> bool {
+        self.comment_whitespace();
+
+        match self.cur() {
+            // `{}`, `{ a, }`
+            Some(b'}') => return true,
+            Some(b'.') if self.source[self.pos..].starts_with(b"...") => {
+                // { ...require('a') }, { ...a }
+                let spread_start = self.pos;
+                self.next_offset(3);
+                self.last_token_pos = Some(self.pos - 1);
+                self.comment_whitespace();
+
+                let value_start = self.pos;
+                if self.at_word(REQUIRE) {
+                    if let Some(record) = self.try_parse_require() {
+                        let statement_span = self.span_from(spread_start);
+                        self.comment_whitespace();
+                        if matches!(self.cur(), Some(b',' | b'}')) {
+                            self.add_reexport(ReexportRecord {
+                                specifier: record.specifier,
+                                span: record.span,
+                                statement_span,
+                            });
+                            return true;
+                        }
                     } else {
-                        // TODO: report error
-                        self.pos = revert_pos;
-                        return;
+                        self.pos = value_start;
                     }
                 }
-                self.parse_result.exports.push(identifier);
+                return self.skip_literal_value();
             }
+            // { [a]: x }
+            Some(b'[') => return self.skip_literal_value(),
+            _ => {}
+        }
 
+        // { *a() {} }
+        let generator = self.eat(b'*').is_some();
+        if generator {
             self.comment_whitespace();
+        }
 
-            if self.cur() == Some(b',') {
-                self.next();
-            } else if self.cur() == Some(b'}') {
-                self.expect_expression = false;
+        let (mut key, mut is_identifier) = match self.literal_property_key() {
+            Some(key) => key,
+            None if generator && self.cur() == Some(b'[') => return self.skip_literal_value(),
+            None => return false,
+        };
+        self.comment_whitespace();
+
+        // { get a() {} }, { set a(x) {} }, { async a() {} }, { async *a() {} }
+        if !generator
+            && is_identifier
+            && matches!(key.0.as_str(), "get" | "set" | "async")
+            && !matches!(self.cur(), Some(b':' | b'(' | b',' | b'}'))
+        {
+            if key.0 == "async" && self.eat(b'*').is_some() {
+                self.comment_whitespace();
+            }
+            if self.cur() == Some(b'[') {
+                return self.skip_literal_value();
+            }
+            (key, is_identifier) = match self.literal_property_key() {
+                Some(key) => key,
+                None => return false,
+            };
+            self.comment_whitespace();
+        }
+
+        match self.cur() {
+            // { a }
+            Some(b',' | b'}') if is_identifier && !generator => {}
+            // { a: x }
+            Some(b':') => {
+                self.last_token_pos = Some(self.pos);
                 self.next();
-                return;
-            } else {
-                // TODO: report error
-                return;
+                // { __esModule: true }
+                if key.0 == ES_MODULE && self.at_literal_true() {
+                    names.push(key);
+                    return self.skip_literal_value();
+                }
+                if !self.skip_literal_value() {
+                    return false;
+                }
+            }
+            // { a() {} }
+            Some(b'(') => {
+                if !self.skip_literal_value() {
+                    return false;
+                }
+            }
+            _ => return false,
+        }
+
+        // `__esModule` only counts if it is `true`
+        if key.0 != ES_MODULE {
+            names.push(key);
+        }
+        true
+    }
+
+    /// Whether the value at `self.pos` is `true` or `!0` up to the next `,` or `}`,
+    /// without consuming it
+    fn at_literal_true(&mut self) -> bool {
+        let revert_pos = self.pos;
+        let errors_len = self.parse_result.errors.len();
+
+        self.comment_whitespace();
+        let is_true = self.eat_true().is_some() && {
+            self.comment_whitespace();
+            matches!(self.cur(), Some(b',' | b'}'))
+        };
+
+        self.pos = revert_pos;
+        self.parse_result.errors.truncate(errors_len);
+        is_true
+    }
+
+    /// Identifier, string or numeric key, and whether it is an identifier
+    fn literal_property_key(&mut self) -> Option<((String, Span), bool)> {
+        let start = self.pos;
+
+        if let Some(key) = self.property_key() {
+            let is_identifier = !matches!(self.source[start], b'\'' | b'"');
+            return Some((key, is_identifier));
+        }
+
+        self.pos = start;
+        let literal = self.number_literal()?;
+        let name = numeric_property_name(&literal)?;
+        Some(((name, self.span_from(start)), false))
+    }
+
+    /// Scans a value of `module.exports = { ... }` like the main loop, so that `require` calls in
+    /// functions, arrows, calls and nested objects are still found.
+    /// Returns whether the `,` or `}` after it is reached.
+    fn skip_literal_value(&mut self) -> bool {
+        let depth = self.bracket_stack.len();
+
+        loop {
+            self.comment_whitespace();
+
+            match self.cur() {
+                Some(b',' | b'}') if self.bracket_stack.len() == depth => return true,
+                Some(c) if self.bracket_stack.len() >= depth => self.parse_token(c),
+                _ => return false,
             }
         }
     }
 
-    fn try_parse_exports_dot_assign(&mut self, assign: bool) {
+    /// Adds the keys read by `try_parse_literal_exports`, where the statement ends at the current position.
+    fn add_literal_exports(&mut self, names: Vec<(String, Span)>, statement_start: usize) {
+        let statement_span = Span {
+            start: statement_start,
+            end: self.pos.max(statement_start),
+        };
+
+        for (name, span) in names {
+            self.add_export(ExportRecord {
+                name,
+                span,
+                statement_span,
+                raw_name: None,
+            });
+        }
+    }
+
+    /// `statement_start` is where `exports`, or `module` of `module.exports`, starts.
+    fn try_parse_exports_dot_assign(&mut self, assign: bool, statement_start: usize) {
+        if !assign && self.exports_stale {
+            self.next_offset(EXPORTS.len());
+            return;
+        }
+        self.try_parse_exports_object_assign(EXPORTS.len(), assign, statement_start);
+    }
+
+    /// Like `try_parse_exports_dot_assign`, where the exports object at `self.pos` is
+    /// `object_len` long, like an alias from `var e = exports`.
+    fn try_parse_exports_object_assign(
+        &mut self,
+        object_len: usize,
+        assign: bool,
+        statement_start: usize,
+    ) {
         // lexer.c tryParseExportsDotAssign
+        let is_word = self.at_word(&self.source[self.pos..self.pos + object_len]);
 
-        self.next_offset(EXPORTS.len()); // after `exports`
+        self.next_offset(object_len); // after `exports`
         let revert_pos = self.pos - 1;
         // at `exports.`
         //           ^
@@ -457,10 +1165,10 @@ impl<'a> Parser<'a> {
                 // exports.asdf
                 self.next();
                 self.comment_whitespace();
-                if let Some(identifier) = self.identifer() {
+                if let Some((identifier, span)) = self.identifier_with_span() {
                     self.comment_whitespace();
                     if self.cur() == Some(b'=') {
-                        self.parse_result.exports.push(identifier);
+                        self.add_assigned_export(identifier, span, statement_start);
                     }
                 }
             }
@@ -469,7 +1177,9 @@ impl<'a> Parser<'a> {
                 self.next();
                 self.comment_whitespace();
 
+                let key_start = self.pos;
                 if let Some(key) = self.string_literal(false) {
+                    let span = self.span_from(key_start);
                     self.comment_whitespace();
 
                     if let Some(b']') = self.cur() {
@@ -477,7 +1187,7 @@ impl<'a> Parser<'a> {
                         self.comment_whitespace();
 
                         if let Some(b'=') = self.cur() {
-                            self.parse_result.exports.push(key);
+                            self.add_assigned_export(key, span, statement_start);
                             return;
                         }
                     } else {
@@ -485,70 +1195,1465 @@ impl<'a> Parser<'a> {
                     }
                 };
             }
-            Some(b'=') if assign => {
+            // Not `module.exports == x`
+            Some(b'=') if assign && self.cur_offset(1) != Some(b'=') => {
                 // module.exports =
                 // notice that `exports = ...` is not exported https://nodejs.org/api/modules.html#exports-shortcut
+                if self.options.reconcile_exports && self.is_top_level_statement() {
+                    self.reset_exports();
+                }
                 self.next();
                 self.comment_whitespace();
+                self.skip_export_alias_assignments();
 
                 // { ... }
-                if self.cur() == Some(b'{') {
-                    self.try_parse_literal_exports();
+                if self.cur() == Some(b'{') && !self.in_literal_exports {
+                    self.try_parse_literal_exports(statement_start);
                     return;
                 }
 
                 if self.cur() == Some(b'r') {
-                    if let Some(module) = self.try_parse_require() {
-                        self.parse_result.reexports = vec![module];
+                    let top_level = self.is_top_level_statement();
+                    if let Some(record) = self.try_parse_require() {
+                        // The last one wins, like upstream
+                        let record = ReexportRecord {
+                            specifier: record.specifier,
+                            span: record.span,
+                            statement_span: self.span_from(statement_start),
+                        };
+                        self.parse_result.reexports = vec![record.specifier.clone()];
+                        self.parse_result.reexport_records = vec![record.clone()];
+                        self.parse_result
+                            .reexport_candidates
+                            .push(ReexportCandidate { record, top_level });
+                        return;
+                    }
+                }
+
+                if self.at_word(TO_COMMON_JS) {
+                    // esbuild: module.exports = __toCommonJS(src_exports);
+                    if let Some(records) = self.try_parse_to_common_js() {
+                        // `__toCommonJS` defines `__esModule` on the exports object
+                        self.parse_result.es_module = true;
+                        for record in records {
+                            self.push_export_once(record);
+                        }
                         return;
                     }
                 }
             }
+            Some(b'=') => {}
+            // Not `var x = exportsFoo`
+            _ if is_word => self.try_backtrack_add_export_alias(statement_start),
             _ => {}
         }
         self.pos = revert_pos;
     }
 
-    fn try_parse_module_exports_dot_assign(&mut self) {
-        // lexer.c tryParseModuleExportsDotAssign
+    /// `exports = ` and `e = ` of `module.exports = exports = { ... }`, `module.exports = e = { ... }`,
+    /// where `e` becomes an alias at top level
+    fn skip_export_alias_assignments(&mut self) {
+        loop {
+            let revert_pos = self.pos;
+            let id_start = self.pos;
+            let id = match self.identifer() {
+                Some(id) => id,
+                None => {
+                    self.pos = revert_pos;
+                    return;
+                }
+            };
+            let id_end = self.pos;
+            self.comment_whitespace();
 
-        self.next_offset(MODULE.len());
-        let revert_pos = self.pos - 1;
+            if self.cur() != Some(b'=') || matches!(self.cur_offset(1), Some(b'=' | b'>')) {
+                self.pos = revert_pos;
+                return;
+            }
+            if id.as_bytes() == EXPORTS {
+                self.exports_stale = false;
+            }
+            // Escaped identifiers are not aliases
+            if id.as_bytes() != EXPORTS
+                && id.as_bytes() == &self.source[id_start..id_end]
+                && self.bracket_stack.is_empty()
+            {
+                self.export_aliases.insert(id);
+            }
 
-        self.comment_whitespace();
-        if self.cur() == Some(b'.') {
             self.next();
-
             self.comment_whitespace();
-
-            if self.source[self.pos..].starts_with(EXPORTS) {
-                return self.try_parse_exports_dot_assign(true);
-            }
         }
-        self.pos = revert_pos;
     }
 
-    fn try_parse_require(&mut self) -> Option<String> {
-        if self.source[self.pos..].starts_with(REQUIRE) {
-            self.next_offset(REQUIRE.len());
-            self.comment_whitespace();
-            if let Some(b'(') = self.cur() {
-                self.next();
+    /// `e.a = ...` or `e['a'] = ...` where `e` is an alias of the exports object,
+    /// from `var e = module.exports` or `module.exports = e = { ... }`.
+    /// `e = x` ends the alias, unless `x` is the exports object.
+    fn try_parse_export_alias(&mut self) {
+        let start = self.pos;
+        match self.export_alias_len() {
+            Some(len) if self.at_alias_assignment(len) => {
+                // e = { ... }
+                self.next_offset(len);
+                let revert_pos = self.pos;
+                let errors_len = self.parse_result.errors.len();
 
                 self.comment_whitespace();
-
-                let required = self.string_literal(false)?;
-                self.parse_result.imports.push(required.clone());
-
+                self.next();
                 self.comment_whitespace();
-
-                if let Some(b')') = self.cur() {
-                    self.next();
-                    self.expect_expression = false;
-                    return Some(required);
-                } else {
-                    // TODO: add errors
-                    return None;
+                let still_alias = self.export_alias_len().is_some()
+                    || (self.read_exports_or_module_dot_exports() && {
+                        self.comment_whitespace();
+                        !matches!(self.cur(), Some(b'.' | b'[' | b'('))
+                    });
+
+                self.pos = revert_pos;
+                self.parse_result.errors.truncate(errors_len);
+                if !still_alias {
+                    let alias = String::from_utf8_lossy(&self.source[start..start + len]);
+                    self.export_aliases.remove(alias.as_ref());
+                }
+            }
+            Some(len) => {
+                self.try_parse_exports_object_assign(len, false, start);
+                // It reverts to the last char of the alias, which is its start for `e`
+                if self.pos == start {
+                    self.next();
+                }
+            }
+            None => self.next(),
+        }
+    }
+
+    /// Aliases are only declared at top level, and only used there, as a nested scope may
+    /// declare another binding of the same name, like `function f(e) { e.a = 1 }`
+    /// Whether the alias of `len` at `self.pos` is followed by `=`, but not `==` or `=>`
+    fn at_alias_assignment(&mut self, len: usize) -> bool {
+        let revert_pos = self.pos;
+        let errors_len = self.parse_result.errors.len();
+
+        self.next_offset(len);
+        self.comment_whitespace();
+        let assignment =
+            self.cur() == Some(b'=') && !matches!(self.cur_offset(1), Some(b'=' | b'>'));
+
+        self.pos = revert_pos;
+        self.parse_result.errors.truncate(errors_len);
+        assignment
+    }
+
+    fn export_alias_len(&self) -> Option<usize> {
+        if !self.bracket_stack.is_empty() {
+            return None;
+        }
+        let len = self.source[self.pos..]
+            .iter()
+            .take_while(|c| c.is_ascii_alphanumeric() || matches!(c, b'_' | b'$'))
+            .count();
+        let id = std::str::from_utf8(&self.source[self.pos..self.pos + len]).ok()?;
+        let followed_by_identifier = self
+            .source
+            .get(self.pos + len)
+            .is_some_and(|c| *c >= 0x80 || *c == b'\\');
+
+        (len > 0 && !followed_by_identifier && self.export_aliases.contains(id)).then_some(len)
+    }
+
+    fn try_parse_module_exports_dot_assign(&mut self) {
+        // lexer.c tryParseModuleExportsDotAssign
+
+        let module_pos = self.pos;
+        self.next_offset(MODULE.len());
+        let revert_pos = self.pos - 1;
+
+        self.comment_whitespace();
+        if self.cur() == Some(b'.') {
+            self.next();
+
+            self.comment_whitespace();
+
+            if self.at_word(EXPORTS) {
+                if self.is_esbuild_annotation(module_pos) {
+                    // The annotation repeats names already found in `__export(...)`
+                    let known = self.parse_result.exports.len();
+                    self.try_parse_exports_dot_assign(true, module_pos);
+                    self.parse_result.exports.truncate(known);
+                    for record in self.parse_result.export_records.split_off(known) {
+                        self.push_export_once(record);
+                    }
+                    return;
+                }
+                return self.try_parse_exports_dot_assign(true, module_pos);
+            }
+        }
+        self.pos = revert_pos;
+    }
+
+    /// Whether `module` at `module_pos` is preceded by `0 && (`, which is how esbuild annotates
+    /// export names for CommonJS lexers: `0 && (module.exports = { a, b, ...require('c') });`
+    fn is_esbuild_annotation(&self, module_pos: usize) -> bool {
+        let source = self.source;
+        let skip_whitespace_back = |mut pos: usize| {
+            while pos > 0 && matches!(source[pos - 1], b'\x09'..=b'\x0d' | b' ') {
+                pos -= 1;
+            }
+            pos
+        };
+
+        let pos = skip_whitespace_back(module_pos);
+        if !source[..pos].ends_with(b"(") {
+            return false;
+        }
+        let pos = skip_whitespace_back(pos - 1);
+        if !source[..pos].ends_with(b"&&") {
+            return false;
+        }
+        let pos = skip_whitespace_back(pos - 2);
+        if !source[..pos].ends_with(b"0") {
+            return false;
+        }
+
+        pos == self.start_pos + 1 || is_br_or_ws_or_puntuator_not_dot(source[pos - 2])
+    }
+
+    /// `module.exports = ...` with `ParserOptions::reconcile_exports`
+    fn reset_exports(&mut self) {
+        self.parse_result.exports.clear();
+        self.parse_result.export_records.clear();
+        self.parse_result.reexports.clear();
+        self.parse_result.reexport_records.clear();
+        self.parse_result.es_module = false;
+        // They are aliases of the previous exports object, like `exports`
+        self.export_aliases.clear();
+        self.exports_stale = true;
+    }
+
+    /// `delete exports.a`, `delete module.exports['a']` with `ParserOptions::reconcile_exports`
+    fn try_parse_delete_export(&mut self) {
+        self.next_offset(DELETE.len());
+        let revert_pos = self.pos - 1;
+        self.comment_whitespace();
+
+        let module = self.eat_bytes(MODULE).is_some();
+        if module {
+            self.comment_whitespace();
+            if self.eat(b'.').is_none() {
+                self.pos = revert_pos;
+                return;
+            }
+            self.comment_whitespace();
+        }
+        if !self.at_word(EXPORTS) || (!module && self.exports_stale) {
+            self.pos = revert_pos;
+            return;
+        }
+        self.next_offset(EXPORTS.len());
+        self.comment_whitespace();
+
+        let name = match self.cur() {
+            Some(b'.') => {
+                self.next();
+                self.comment_whitespace();
+                self.identifer()
+            }
+            Some(b'[') => {
+                self.next();
+                self.comment_whitespace();
+                let key = self.string_literal(false);
+                self.comment_whitespace();
+                self.eat(b']').and(key)
+            }
+            _ => None,
+        };
+
+        match name {
+            Some(name) => {
+                if name == ES_MODULE {
+                    self.parse_result.es_module = false;
+                }
+                self.parse_result.exports.retain(|export| *export != name);
+                self.parse_result
+                    .export_records
+                    .retain(|record| record.name != name);
+            }
+            None => self.pos = revert_pos,
+        }
+    }
+
+    fn push_export_once(&mut self, record: ExportRecord) {
+        if !self.parse_result.exports.contains(&record.name) {
+            self.add_export(record);
+        }
+    }
+
+    /// `__esModule` is only passed in when its value is known to be `true`.
+    fn add_export(&mut self, mut record: ExportRecord) {
+        if record.name == ES_MODULE {
+            self.parse_result.es_module = true;
+            if !self.options.es_module_export {
+                return;
+            }
+        }
+        if self.options.reconcile_exports && self.parse_result.exports.contains(&record.name) {
+            return;
+        }
+        if self.options.raw_names && record.raw_name.is_none() {
+            record.raw_name = Some(self.raw_name(&record));
+        }
+        self.parse_result.exports.push(record.name.clone());
+        self.parse_result.export_records.push(record);
+    }
+
+    /// Adds the export of `exports.name = ...`, `self.pos` being at `=`.
+    /// `exports.__esModule` only counts if it is assigned `true`.
+    fn add_assigned_export(&mut self, name: String, span: Span, statement_start: usize) {
+        if name == ES_MODULE {
+            let revert_pos = self.pos;
+            self.next();
+            self.comment_whitespace();
+            let is_true = self.eat_true().is_some();
+            self.pos = revert_pos;
+
+            if !is_true {
+                return;
+            }
+        }
+        self.add_export(ExportRecord {
+            name,
+            span,
+            statement_span: Span {
+                start: statement_start,
+                end: self.pos + 1,
+            },
+            raw_name: None,
+        });
+    }
+
+    /// Reads the string literal at `record.span` again, without decoding it lossily
+    fn raw_name(&mut self, record: &ExportRecord) -> Vec<u8> {
+        // `{ 0x10: a }` is named `16`
+        if matches!(self.source.get(record.span.start), Some(b'0'..=b'9' | b'.')) {
+            return record.name.clone().into_bytes();
+        }
+        if !matches!(self.source.get(record.span.start), Some(b'\'' | b'"')) {
+            let identifier = &self.source[record.span.start..record.span.end];
+            if identifier.contains(&b'\\') {
+                // Escapes are always valid UTF-8
+                return record.name.clone().into_bytes();
+            }
+            return identifier.to_vec();
+        }
+
+        let pos = self.pos;
+        let errors = self.parse_result.errors.len();
+
+        self.pos = record.span.start;
+        let raw_name = self.string_literal_bytes(false);
+
+        self.pos = pos;
+        self.parse_result.errors.truncate(errors);
+
+        raw_name.unwrap_or_else(|| record.name.clone().into_bytes())
+    }
+
+    fn add_reexport(&mut self, record: ReexportRecord) {
+        if self.options.reconcile_exports && self.parse_result.reexports.contains(&record.specifier)
+        {
+            return;
+        }
+        self.parse_result.reexports.push(record.specifier.clone());
+        self.parse_result.reexport_records.push(record);
+    }
+
+    /// `true`, or `!0` as minifiers write it
+    fn eat_true(&mut self) -> Option<()> {
+        if self.eat_word(b"true").is_some() {
+            return Some(());
+        }
+        self.eat(b'!')?;
+        self.comment_whitespace();
+        self.eat_word(b"0")
+    }
+
+    fn read_exports_or_module_dot_exports(&mut self) -> bool {
+        // lexer.c readExportsOrModuleDotExports
+        let revert_pos = self.pos;
+        let module = self.at_word(MODULE);
+
+        if module {
+            self.next_offset(MODULE.len());
+            self.comment_whitespace();
+            if self.eat(b'.').is_none() {
+                self.pos = revert_pos;
+                return false;
+            }
+            self.comment_whitespace();
+        }
+
+        if (module || !self.exports_stale) && self.eat_word(EXPORTS).is_some() {
+            return true;
+        }
+
+        self.pos = revert_pos;
+        false
+    }
+
+    fn try_parse_object_define_or_keys(&mut self, keys: bool) {
+        // lexer.c tryParseObjectDefineOrKeys
+        let statement_start = self.pos;
+        self.next_offset(OBJECT.len());
+        let revert_pos = self.pos - 1;
+
+        self.comment_whitespace();
+
+        if self.eat(b'.').is_some() {
+            self.comment_whitespace();
+
+            if self.at_word(DEFINE_PROPERTY) {
+                if let Some((name, span)) = self.try_parse_define_property() {
+                    self.add_export(ExportRecord {
+                        name,
+                        span,
+                        statement_span: self.span_from(statement_start),
+                        raw_name: None,
+                    });
+                }
+            } else if keys && self.at_word(KEYS) {
+                if let Some(record) = self.try_parse_object_keys_reexport() {
+                    self.add_reexport(ReexportRecord {
+                        specifier: record.specifier,
+                        span: record.span,
+                        statement_span: self.span_from(statement_start),
+                    });
+                }
+            } else if (self.at_word(ASSIGN) || self.at_word(DEFINE_PROPERTIES))
+                && self.try_parse_object_assign(statement_start)
+            {
+                return;
+            }
+        }
+
+        self.pos = revert_pos;
+    }
+
+    /// Matches `assign` and `defineProperties` calls on the exports object:
+    ///
+    /// ```js
+    /// Object.assign(module.exports, { a, b }, require('c'))
+    /// Object.defineProperties(exports, { a: { enumerable: true, get() { return a; } } })
+    /// ```
+    ///
+    /// Keys of object literals are exports, like in `module.exports = { ... }`, and `require`
+    /// calls passed to `assign` are reexports. Keys of `defineProperties` also need a descriptor
+    /// like the ones of `defineProperty`. Once an argument is something else, like a call,
+    /// the main loop scans the rest of the call.
+    ///
+    /// Returns whether the call matched, in which case the main loop continues from the current
+    /// position, with the `(` of the call still open unless its `)` is reached.
+    fn try_parse_object_assign(&mut self, statement_start: usize) -> bool {
+        // The values of `module.exports = { ... }` are scanned by the main loop, which must not
+        // come back here
+        if self.in_literal_exports {
+            return false;
+        }
+
+        let assign = self.at_word(ASSIGN);
+        let method = if assign { ASSIGN } else { DEFINE_PROPERTIES };
+        if self.method_call_exports(method).is_none() {
+            return false;
+        }
+
+        self.bracket_stack
+            .push(Bracket::Parenthesis(ParenthesisType::Plain));
+        self.last_token_pos = Some(self.pos - 1);
+
+        // `defineProperties` only takes the descriptors object
+        let mut literal_allowed = true;
+
+        loop {
+            let argument_start = self.pos;
+
+            match self.cur() {
+                Some(b'{') if literal_allowed => {
+                    let closed = if assign {
+                        self.try_parse_literal_exports(statement_start)
+                    } else {
+                        self.try_parse_property_descriptors(statement_start)
+                    };
+                    if !closed {
+                        return true;
+                    }
+                }
+                Some(b'r') if assign && self.at_word(REQUIRE) => {
+                    let record = match self.try_parse_require() {
+                        Some(record) => record,
+                        None => {
+                            self.pos = argument_start;
+                            return true;
+                        }
+                    };
+                    self.last_token_pos = Some(self.pos - 1);
+                    let statement_span = self.span_from(statement_start);
+                    self.comment_whitespace();
+                    if !matches!(self.cur(), Some(b',' | b')')) {
+                        // require('c').d
+                        return true;
+                    }
+                    self.add_reexport(ReexportRecord {
+                        specifier: record.specifier,
+                        span: record.span,
+                        statement_span,
+                    });
+                }
+                // Object.assign(exports, _a, { b })
+                _ if assign && self.identifer().is_some() => {
+                    self.comment_whitespace();
+                    if !matches!(self.cur(), Some(b',' | b')')) {
+                        self.pos = argument_start;
+                        return true;
+                    }
+                    self.last_token_pos = Some(self.pos - 1);
+                }
+                _ => {
+                    self.pos = argument_start;
+                    return true;
+                }
+            }
+            literal_allowed = assign;
+
+            self.comment_whitespace();
+            match self.cur() {
+                Some(b',') => {
+                    self.last_token_pos = Some(self.pos);
+                    self.next();
+                    self.comment_whitespace();
+                }
+                Some(b')') => {
+                    self.pop_bracket_stack();
+                    return true;
+                }
+                _ => return true,
+            }
+        }
+    }
+
+    /// The descriptors object of `Object.defineProperties(exports, { ... })`, where a key is an
+    /// export if its descriptor is one that `try_parse_define_property` accepts.
+    /// Returns whether the closing `}` is reached.
+    fn try_parse_property_descriptors(&mut self, statement_start: usize) -> bool {
+        // Like `try_parse_literal_exports`, the brace stays open if the end is not reached
+        self.bracket_stack.push(Bracket::Brace(BraceType::Plain));
+        self.last_token_pos = Some(self.pos);
+        self.next();
+        self.in_literal_exports = true;
+
+        let mut names: Vec<(String, Span)> = vec![];
+        let closed = loop {
+            self.comment_whitespace();
+            if self.cur() == Some(b'}') {
+                self.pop_bracket_stack();
+                break true;
+            }
+
+            let key = match self.literal_property_key() {
+                Some((key, _)) => key,
+                None => break false,
+            };
+            self.comment_whitespace();
+            if self.cur() != Some(b':') {
+                break false;
+            }
+            self.last_token_pos = Some(self.pos);
+            self.next();
+            self.comment_whitespace();
+
+            if self.is_export_descriptor(&key.0) {
+                names.push(key);
+            }
+            if !self.skip_literal_value() {
+                break false;
+            }
+
+            match self.cur() {
+                Some(b',') => {
+                    self.last_token_pos = Some(self.pos);
+                    self.next();
+                }
+                _ => {
+                    // `}`
+                    self.pop_bracket_stack();
+                    break true;
+                }
+            }
+        };
+
+        self.in_literal_exports = false;
+        self.add_literal_exports(names, statement_start);
+        closed
+    }
+
+    /// Whether the descriptor at `self.pos` exports `name`, see `export_descriptor`,
+    /// without consuming it
+    fn is_export_descriptor(&mut self, name: &str) -> bool {
+        let revert_pos = self.pos;
+        let errors_len = self.parse_result.errors.len();
+
+        let is_export = self.export_descriptor(name).is_some();
+
+        self.pos = revert_pos;
+        self.parse_result.errors.truncate(errors_len);
+        is_export
+    }
+
+    /// A property descriptor that exports `name`, like the ones of `try_parse_define_property`.
+    /// `__esModule` needs `{ value: true }`. A getter is read up to the closing `}`, a value only
+    /// up to `value:`. Returns whether it is a getter.
+    fn export_descriptor(&mut self, name: &str) -> Option<bool> {
+        self.eat(b'{')?;
+        self.comment_whitespace();
+
+        if self.eat_word(b"enumerable").is_some() {
+            self.enumerable_true()?;
+        }
+
+        if self.eat_word(b"value").is_some() {
+            // { value: ... }
+            self.comment_whitespace();
+            self.eat(b':')?;
+            if name == ES_MODULE {
+                // { value: true }
+                self.comment_whitespace();
+                self.eat_true()?;
+            }
+            return Some(false);
+        }
+        if name == ES_MODULE {
+            // The value of a getter is not known
+            return None;
+        }
+
+        self.getter_return()?;
+        self.identifer()?;
+        self.comment_whitespace();
+
+        match self.cur() {
+            Some(b'.') => {
+                // return m.a
+                self.next();
+                self.comment_whitespace();
+                self.identifer()?;
+                self.comment_whitespace();
+            }
+            Some(b'[') => {
+                // return m['a']
+                self.next();
+                self.comment_whitespace();
+                self.string_literal(true)?;
+                self.comment_whitespace();
+                self.eat(b']')?;
+                self.comment_whitespace();
+            }
+            _ => {}
+        }
+
+        self.getter_end()?;
+
+        Some(true)
+    }
+
+    /// Matches the `defineProperty` call shapes emitted by TypeScript and Babel:
+    ///
+    /// ```js
+    /// Object.defineProperty(exports, 'a', { enumerable: true, get: function () { return a; } })
+    /// Object.defineProperty(exports, 'a', { enumerable: true, get() { return m.a; } })
+    /// Object.defineProperty(module.exports, 'a', { value: a })
+    /// ```
+    ///
+    /// Returns the property name and its span if the call matches, otherwise `None`.
+    fn try_parse_define_property(&mut self) -> Option<(String, Span)> {
+        self.method_call_exports(DEFINE_PROPERTY)?;
+
+        let name_start = self.pos;
+        let name = self.string_literal(false)?;
+        let span = self.span_from(name_start);
+
+        self.comment_whitespace();
+        self.eat(b',')?;
+        self.comment_whitespace();
+
+        if self.export_descriptor(&name)? {
+            self.comment_whitespace();
+            self.eat(b')')?;
+        }
+
+        Some((name, span))
+    }
+
+    /// `defineProperty(exports, ` and the like, up to the next argument
+    fn method_call_exports(&mut self, method: &[u8]) -> Option<()> {
+        self.next_offset(method.len());
+        self.comment_whitespace();
+        self.eat(b'(')?;
+        self.comment_whitespace();
+
+        if !self.read_exports_or_module_dot_exports() {
+            return None;
+        }
+
+        self.comment_whitespace();
+        self.eat(b',')?;
+        self.comment_whitespace();
+
+        Some(())
+    }
+
+    /// `: true, ` after `enumerable`
+    fn enumerable_true(&mut self) -> Option<()> {
+        self.comment_whitespace();
+        self.eat(b':')?;
+        self.comment_whitespace();
+        self.eat_word(b"true")?;
+        self.comment_whitespace();
+        self.eat(b',')?;
+        self.comment_whitespace();
+
+        Some(())
+    }
+
+    /// `get: function () { return ` or `get() { return `
+    fn getter_return(&mut self) -> Option<()> {
+        self.eat_word(b"get")?;
+        self.comment_whitespace();
+
+        if self.eat(b':').is_some() {
+            // { get: function () { ... } }
+            self.comment_whitespace();
+            self.eat_word(b"function")?;
+            self.comment_whitespace();
+            // optional function name
+            self.identifer();
+            self.comment_whitespace();
+        }
+
+        self.eat(b'(')?;
+        self.comment_whitespace();
+        self.eat(b')')?;
+        self.comment_whitespace();
+        self.eat(b'{')?;
+        self.comment_whitespace();
+        self.eat_word(b"return")?;
+        self.comment_whitespace();
+
+        Some(())
+    }
+
+    /// `; } }` after the returned expression of a getter
+    fn getter_end(&mut self) -> Option<()> {
+        if self.eat(b';').is_some() {
+            self.comment_whitespace();
+        }
+        self.eat(b'}')?;
+        self.comment_whitespace();
+        if self.eat(b',').is_some() {
+            self.comment_whitespace();
+        }
+        self.eat(b'}')
+    }
+
+    /// Matches Babel's output for `export * from 'x'`, given that `_x` is bound by `var _x = require('x')`:
+    ///
+    /// ```js
+    /// Object.keys(_x).forEach(function (key) {
+    ///   if (key === "default" || key === "__esModule") return;
+    ///   if (Object.prototype.hasOwnProperty.call(_exportNames, key)) return;
+    ///   if (key in exports && exports[key] === _x[key]) return;
+    ///   exports[key] = _x[key];
+    /// });
+    /// ```
+    ///
+    /// Both later `if` guards are optional. The assignment may also be written as
+    /// `Object.defineProperty(exports, key, { enumerable: true, get: function () { return _x[key]; } })`,
+    /// and older Babel guards it with `if (key !== "default" && !_exportNames.hasOwnProperty(key))`.
+    ///
+    /// Returns the `require` `_x` is bound to.
+    fn try_parse_object_keys_reexport(&mut self) -> Option<ImportRecord> {
+        self.next_offset(KEYS.len());
+        self.comment_whitespace();
+        self.eat(b'(')?;
+        self.comment_whitespace();
+        let it_id = self.identifer()?;
+        self.comment_whitespace();
+        self.eat(b')')?;
+        self.comment_whitespace();
+        self.eat(b'.')?;
+        self.comment_whitespace();
+        self.eat_word(b"forEach")?;
+        self.comment_whitespace();
+        self.eat(b'(')?;
+        self.comment_whitespace();
+        self.eat_word(b"function")?;
+        self.comment_whitespace();
+        self.eat(b'(')?;
+        self.comment_whitespace();
+        let id = self.identifer()?;
+        self.comment_whitespace();
+        self.eat(b')')?;
+        self.comment_whitespace();
+        self.eat(b'{')?;
+        self.comment_whitespace();
+        self.eat_word(IF)?;
+        self.comment_whitespace();
+        self.eat(b'(')?;
+        self.comment_whitespace();
+        self.eat_identifier(&id)?;
+        self.comment_whitespace();
+
+        if self.eat_bytes(b"===").is_some() {
+            // if (key === "default" || key === "__esModule") return;
+            self.comment_whitespace();
+            self.eat_string("default")?;
+            self.comment_whitespace();
+            self.eat_bytes(b"||")?;
+            self.comment_whitespace();
+            self.eat_identifier(&id)?;
+            self.comment_whitespace();
+            self.eat_bytes(b"===")?;
+            self.comment_whitespace();
+            self.eat_string("__esModule")?;
+            self.comment_whitespace();
+            self.eat(b')')?;
+            self.comment_whitespace();
+            self.return_statement()?;
+
+            if self.eat_word(IF).is_some() {
+                self.comment_whitespace();
+                self.eat(b'(')?;
+                self.comment_whitespace();
+
+                let mut key_in_exports = true;
+                if self.at_word(OBJECT) {
+                    // if (Object.prototype.hasOwnProperty.call(_exportNames, key)) return;
+                    self.object_has_own_property_call(&id)?;
+                    self.comment_whitespace();
+                    self.eat(b')')?;
+                    self.comment_whitespace();
+                    self.return_statement()?;
+
+                    key_in_exports = self.eat_word(IF).is_some();
+                    if key_in_exports {
+                        self.comment_whitespace();
+                        self.eat(b'(')?;
+                        self.comment_whitespace();
+                    }
+                }
+
+                if key_in_exports {
+                    // if (key in exports && exports[key] === _x[key]) return;
+                    self.eat_identifier(&id)?;
+                    self.comment_whitespace();
+                    self.eat_word(b"in")?;
+                    self.comment_whitespace();
+                    if !self.read_exports_or_module_dot_exports() {
+                        return None;
+                    }
+                    self.comment_whitespace();
+                    self.eat_bytes(b"&&")?;
+                    self.comment_whitespace();
+                    if !self.read_exports_or_module_dot_exports() {
+                        return None;
+                    }
+                    self.comment_whitespace();
+                    self.computed_member_key(&id)?;
+                    self.eat_bytes(b"===")?;
+                    self.comment_whitespace();
+                    self.eat_identifier(&it_id)?;
+                    self.comment_whitespace();
+                    self.computed_member_key(&id)?;
+                    self.eat(b')')?;
+                    self.comment_whitespace();
+                    self.return_statement()?;
+                }
+            }
+        } else {
+            // if (key !== "default" && !_exportNames.hasOwnProperty(key))
+            self.eat_bytes(b"!==")?;
+            self.comment_whitespace();
+            self.eat_string("default")?;
+            self.comment_whitespace();
+
+            if self.eat_bytes(b"&&").is_some() {
+                self.comment_whitespace();
+                self.eat(b'!')?;
+                self.comment_whitespace();
+                self.identifer()?;
+                self.comment_whitespace();
+                self.eat(b'.')?;
+                self.comment_whitespace();
+                self.eat_word(b"hasOwnProperty")?;
+                self.comment_whitespace();
+                self.eat(b'(')?;
+                self.comment_whitespace();
+                self.eat_identifier(&id)?;
+                self.comment_whitespace();
+                self.eat(b')')?;
+                self.comment_whitespace();
+            }
+
+            self.eat(b')')?;
+            self.comment_whitespace();
+        }
+
+        if self.eat_word(OBJECT).is_some() {
+            // Object.defineProperty(exports, key, { enumerable: true, get: function () { return _x[key]; } });
+            self.comment_whitespace();
+            self.eat(b'.')?;
+            self.comment_whitespace();
+            if !self.at_word(DEFINE_PROPERTY) {
+                return None;
+            }
+            self.method_call_exports(DEFINE_PROPERTY)?;
+            self.eat_identifier(&id)?;
+            self.comment_whitespace();
+            self.eat(b',')?;
+            self.comment_whitespace();
+            self.eat(b'{')?;
+            self.comment_whitespace();
+            self.eat_word(b"enumerable")?;
+            self.enumerable_true()?;
+            self.getter_return()?;
+            self.eat_identifier(&it_id)?;
+            self.comment_whitespace();
+            self.computed_member_key(&id)?;
+            self.getter_end()?;
+            self.comment_whitespace();
+            self.eat(b')')?;
+            self.comment_whitespace();
+        } else {
+            // exports[key] = _x[key];
+            if !self.read_exports_or_module_dot_exports() {
+                return None;
+            }
+            self.comment_whitespace();
+            self.computed_member_key(&id)?;
+            self.eat(b'=')?;
+            self.comment_whitespace();
+            self.eat_identifier(&it_id)?;
+            self.comment_whitespace();
+            self.computed_member_key(&id)?;
+        }
+
+        if self.eat(b';').is_some() {
+            self.comment_whitespace();
+        }
+        self.eat(b'}')?;
+        self.comment_whitespace();
+        self.eat(b')')?;
+
+        self.require_bindings.get(&it_id).cloned()
+    }
+
+    /// `Object.prototype.hasOwnProperty.call(_exportNames, key)`
+    fn object_has_own_property_call(&mut self, id: &str) -> Option<()> {
+        self.eat_word(OBJECT)?;
+        for property in [&b"prototype"[..], b"hasOwnProperty", b"call"] {
+            self.comment_whitespace();
+            self.eat(b'.')?;
+            self.comment_whitespace();
+            self.eat_word(property)?;
+        }
+        self.comment_whitespace();
+        self.eat(b'(')?;
+        self.comment_whitespace();
+        self.identifer()?;
+        self.comment_whitespace();
+        self.eat(b',')?;
+        self.comment_whitespace();
+        self.eat_identifier(id)?;
+        self.comment_whitespace();
+        self.eat(b')')?;
+
+        Some(())
+    }
+
+    /// `[key]` with trailing whitespace
+    fn computed_member_key(&mut self, id: &str) -> Option<()> {
+        self.eat(b'[')?;
+        self.comment_whitespace();
+        self.eat_identifier(id)?;
+        self.comment_whitespace();
+        self.eat(b']')?;
+        self.comment_whitespace();
+
+        Some(())
+    }
+
+    /// `return;` or `return` with trailing whitespace
+    fn return_statement(&mut self) -> Option<()> {
+        self.eat_word(b"return")?;
+        self.comment_whitespace();
+        if self.eat(b';').is_some() {
+            self.comment_whitespace();
+        }
+
+        Some(())
+    }
+
+    /// Records `_x` of `var _x = require('x')` (`let` and `const` alike),
+    /// so that `Object.keys(_x)` can be resolved to the specifier later.
+    /// `require_pos` is where `require` starts.
+    fn try_backtrack_add_require_binding(&mut self, require_pos: usize, record: ImportRecord) {
+        // lexer.c tryBacktrackAddStarExportBinding
+        if let Some(id) = self.backtrack_declared_identifier(require_pos) {
+            self.require_bindings.insert(String::from(id), record);
+        }
+    }
+
+    /// `e` of `var e = module.exports`, `const e = exports` at top level
+    fn try_backtrack_add_export_alias(&mut self, statement_start: usize) {
+        if !self.bracket_stack.is_empty() {
+            return;
+        }
+        if let Some(id) = self.backtrack_declared_identifier(statement_start) {
+            self.export_aliases.insert(String::from(id));
+        }
+    }
+
+    /// `x` of `var x = `, `let x = ` or `const x = ` before `value_pos`
+    fn backtrack_declared_identifier(&self, value_pos: usize) -> Option<&'a str> {
+        let source = self.source;
+        let skip_whitespace_back = |mut pos: usize| {
+            while pos > 0 && matches!(source[pos - 1], b' ' | b'\t') {
+                pos -= 1;
+            }
+            pos
+        };
+
+        let mut pos = skip_whitespace_back(value_pos);
+        if pos == 0 || source[pos - 1] != b'=' {
+            return None;
+        }
+
+        pos = skip_whitespace_back(pos - 1);
+        let id_end = pos;
+        while pos > 0
+            && (source[pos - 1] >= 0x80 || is_identifier_char(char::from(source[pos - 1])))
+        {
+            pos -= 1;
+        }
+
+        let id = std::str::from_utf8(&source[pos..id_end]).ok()?;
+        let mut chars = id.chars();
+        if !chars.next().is_some_and(is_identifier_start) || !chars.all(is_identifier_char) {
+            return None;
+        }
+
+        let declaration_end = skip_whitespace_back(pos);
+        if declaration_end == pos {
+            return None;
+        }
+
+        let declared = [VAR, LET, CONST].iter().any(|keyword| {
+            source[..declaration_end].ends_with(keyword)
+                && (declaration_end == keyword.len()
+                    || is_br_or_ws_or_puntuator_not_dot(
+                        source[declaration_end - keyword.len() - 1],
+                    ))
+        });
+
+        declared.then_some(id)
+    }
+
+    fn try_parse_require(&mut self) -> Option<ImportRecord> {
+        self.try_parse_require_as(Interop::Plain)
+    }
+
+    fn try_parse_require_as(&mut self, interop: Interop) -> Option<ImportRecord> {
+        // lexer.c tryParseRequire
+        if !self.source[self.pos..].starts_with(REQUIRE) {
+            return None;
+        }
+
+        let require_pos = self.pos;
+        self.next_offset(REQUIRE.len());
+        let revert_pos = self.pos - 1;
+
+        if let Some((specifier, span)) = self.require_arguments() {
+            let record = ImportRecord {
+                specifier,
+                interop,
+                span,
+                statement_span: self.span_from(require_pos),
+            };
+            self.parse_result.imports.push(record.specifier.clone());
+            self.parse_result.import_records.push(record.clone());
+            return Some(record);
+        }
+
+        // Not a `require('...')` call. Continue right after `require`, so that
+        // brackets of something like `require(name)` are still balanced.
+        self.pos = revert_pos;
+        None
+    }
+
+    /// `('specifier')` following `require`
+    fn require_arguments(&mut self) -> Option<(String, Span)> {
+        self.comment_whitespace();
+        self.eat(b'(')?;
+        self.comment_whitespace();
+        let start = self.pos;
+        let required = self.string_literal(false)?;
+        let span = self.span_from(start);
+        self.comment_whitespace();
+        self.eat(b')')?;
+
+        Some((required, span))
+    }
+
+    fn try_parse_interop_require(&mut self, helper: &[u8], interop: Interop) {
+        // lexer.c `_interopRequireWildcard(`
+        let helper_pos = self.pos;
+        self.next_offset(helper.len());
+        let revert_pos = self.pos - 1;
+
+        self.comment_whitespace();
+
+        if self.cur() == Some(b'(') {
+            // `_interopRequireDefault(require('x'))`
+            self.open_call_parenthesis();
+
+            if let Some(record) = self.try_parse_require_as(interop) {
+                // var _x = _interopRequireWildcard(require('x'));
+                self.try_backtrack_add_require_binding(helper_pos, record);
+            }
+            return;
+        }
+
+        self.pos = revert_pos;
+    }
+
+    fn try_parse_export_star(&mut self) {
+        // lexer.c `__export(` / `__exportStar(`
+        let statement_start = self.pos;
+        self.next_offset(TS_EXPORT.len());
+        let star = self.source[self.pos..].starts_with(STAR);
+        if star {
+            self.next_offset(STAR.len());
+        }
+        let revert_pos = self.pos - 1;
+
+        self.comment_whitespace();
+
+        if self.cur() == Some(b'(') {
+            // `__exportStar(require('x'), exports)`, `__export(require('x'))`
+            self.open_call_parenthesis();
+
+            if let Some(record) = self.try_parse_require() {
+                self.add_reexport(ReexportRecord {
+                    specifier: record.specifier,
+                    span: record.span,
+                    statement_span: self.span_from(statement_start),
+                });
+            } else if !star {
+                // esbuild: `__export(src_exports, { a: () => a })`
+                let target_pos = self.pos;
+                if let Some((target, records)) = self.try_parse_esbuild_export(statement_start) {
+                    self.esbuild_exports
+                        .entry(target)
+                        .or_default()
+                        .extend(records);
+                }
+                self.pos = target_pos;
+            }
+            return;
+        }
+
+        self.pos = revert_pos;
+    }
+
+    fn try_parse_swc_export(&mut self) {
+        // SWC `_export(exports, { ... })`
+        let statement_start = self.pos;
+        self.next_offset(SWC_EXPORT.len());
+        let revert_pos = self.pos - 1;
+
+        if let Some(names) = self.try_parse_swc_export_object() {
+            let statement_span = self.span_from(statement_start);
+            // The value of a getter is not known
+            for (name, span) in names.into_iter().filter(|(name, _)| name != ES_MODULE) {
+                self.add_export(ExportRecord {
+                    name,
+                    span,
+                    statement_span,
+                    raw_name: None,
+                });
+            }
+        }
+
+        self.pos = revert_pos;
+    }
+
+    /// Matches the call to the `_export` helper SWC emits for named exports:
+    ///
+    /// ```js
+    /// _export(exports, {
+    ///     a: function() {
+    ///         return a;
+    ///     },
+    ///     "b": function() {
+    ///         return _b.default;
+    ///     },
+    ///     get c () {
+    ///         return c;
+    ///     }
+    /// });
+    /// ```
+    ///
+    /// Returns the property names and their spans.
+    fn try_parse_swc_export_object(&mut self) -> Option<Vec<(String, Span)>> {
+        self.comment_whitespace();
+        self.eat(b'(')?;
+        self.comment_whitespace();
+
+        if !self.read_exports_or_module_dot_exports() {
+            return None;
+        }
+
+        self.comment_whitespace();
+        self.eat(b',')?;
+        self.comment_whitespace();
+        self.eat(b'{')?;
+
+        let mut names = vec![];
+        loop {
+            self.comment_whitespace();
+            if self.eat(b'}').is_some() {
+                break;
+            }
+
+            let mut name = self.property_key()?;
+            self.comment_whitespace();
+
+            if name.0 == "get" && self.cur() != Some(b':') {
+                // get a () { ... }
+                name = self.property_key()?;
+                self.comment_whitespace();
+            } else {
+                // a: function() { ... }
+                self.eat(b':')?;
+                self.comment_whitespace();
+                self.eat_word(b"function")?;
+                self.comment_whitespace();
+            }
+
+            self.eat(b'(')?;
+            self.comment_whitespace();
+            self.eat(b')')?;
+            self.comment_whitespace();
+            self.eat(b'{')?;
+            self.comment_whitespace();
+            self.eat_word(b"return")?;
+            self.comment_whitespace();
+            self.identifer()?;
+            self.comment_whitespace();
+            while self.eat(b'.').is_some() {
+                self.comment_whitespace();
+                self.identifer()?;
+                self.comment_whitespace();
+            }
+            if self.eat(b';').is_some() {
+                self.comment_whitespace();
+            }
+            self.eat(b'}')?;
+
+            names.push(name);
+
+            self.comment_whitespace();
+            if self.eat(b',').is_none() {
+                self.eat(b'}')?;
+                break;
+            }
+        }
+
+        self.comment_whitespace();
+        self.eat(b')')?;
+
+        Some(names)
+    }
+
+    /// Matches the object esbuild passes to its `__export` helper:
+    ///
+    /// ```js
+    /// __export(src_exports, {
+    ///   a: () => a,
+    ///   "b": () => import_b.b
+    /// });
+    /// ```
+    ///
+    /// Returns the target and the exports, `statement_start` being where `__export` starts.
+    fn try_parse_esbuild_export(
+        &mut self,
+        statement_start: usize,
+    ) -> Option<(String, Vec<ExportRecord>)> {
+        let target = self.identifer()?;
+        self.comment_whitespace();
+        self.eat(b',')?;
+        self.comment_whitespace();
+        self.eat(b'{')?;
+
+        let mut names = vec![];
+        loop {
+            self.comment_whitespace();
+            if self.eat(b'}').is_some() {
+                break;
+            }
+
+            let name = self.property_key()?;
+
+            self.comment_whitespace();
+            self.eat(b':')?;
+            self.comment_whitespace();
+            self.eat(b'(')?;
+            self.comment_whitespace();
+            self.eat(b')')?;
+            self.comment_whitespace();
+            self.eat_bytes(b"=>")?;
+            self.comment_whitespace();
+            self.identifer()?;
+            self.comment_whitespace();
+            while self.eat(b'.').is_some() {
+                self.comment_whitespace();
+                self.identifer()?;
+                self.comment_whitespace();
+            }
+
+            names.push(name);
+
+            if self.eat(b',').is_none() {
+                self.comment_whitespace();
+                self.eat(b'}')?;
+                break;
+            }
+        }
+
+        let statement_span = self.span_from(statement_start);
+        let records = names
+            .into_iter()
+            .map(|(name, span)| ExportRecord {
+                name,
+                span,
+                statement_span,
+                raw_name: None,
+            })
+            .collect();
+
+        Some((target, records))
+    }
+
+    /// `__toCommonJS(src_exports)`, returning the exports esbuild's `__export` defined on `src_exports`
+    fn try_parse_to_common_js(&mut self) -> Option<Vec<ExportRecord>> {
+        self.next_offset(TO_COMMON_JS.len());
+        self.comment_whitespace();
+        self.eat(b'(')?;
+        self.comment_whitespace();
+        let target = self.identifer()?;
+        self.comment_whitespace();
+        self.eat(b')')?;
+
+        self.esbuild_exports.remove(&target)
+    }
+
+    fn check_import_statement(&mut self) {
+        // lexer.c throwIfImportStatement
+        let start_pos = self.pos;
+        self.next_offset(IMPORT.len());
+        let word_end = self.pos;
+
+        self.comment_whitespace();
+
+        let is_statement = match self.cur() {
+            Some(b'(') => {
+                self.try_parse_dynamic_import();
+                return;
+            }
+            None => false,
+            Some(b'.') => {
+                self.parse_result
+                    .errors
+                    .push(ParseError::UnexpectedImportMeta(ParseErrorMessage {
+                        pos: start_pos,
+                        message: String::from("Unexpected import.meta in CJS module. "),
+                    }));
+                false
+            }
+            // import 'x', import { x } from 'x', import * as x from 'x'
+            Some(b'\'' | b'"' | b'{' | b'*') => true,
+            // import x from 'x'
+            Some(_) => self.pos > word_end,
+        };
+
+        // import statements are only permitted at top level
+        if is_statement && self.bracket_stack.is_empty() {
+            self.parse_result
+                .errors
+                .push(ParseError::UnexpectedImportStatement(ParseErrorMessage {
+                    pos: start_pos,
+                    message: String::from("Unexpected import statement in CJS module. "),
+                }));
+        }
+
+        self.pos = word_end;
+    }
+
+    /// `import('x')`, starting at `(`
+    fn try_parse_dynamic_import(&mut self) {
+        self.open_call_parenthesis();
+
+        let start = self.pos;
+        let errors_len = self.parse_result.errors.len();
+
+        let specifier = match self.cur() {
+            Some(b'\'' | b'"') => self.string_literal(false),
+            Some(b'`') => self.no_substitution_template(),
+            _ => return,
+        };
+
+        match specifier {
+            Some(specifier) => {
+                let end = self.pos;
+                self.comment_whitespace();
+
+                // `import('x', { with: { type: 'json' } })` has options
+                if matches!(self.cur(), Some(b')' | b',')) {
+                    self.parse_result.dynamic_imports.push(DynamicImport {
+                        specifier,
+                        span: Span { start, end },
+                    });
+                }
+            }
+            None => {
+                // Let the main loop scan it and report errors only once
+                self.parse_result.errors.truncate(errors_len);
+                self.pos = start;
+            }
+        }
+    }
+
+    /// Value of a template literal without substitutions, like `` `x` ``
+    fn no_substitution_template(&mut self) -> Option<String> {
+        self.eat(b'`')?;
+
+        let mut result = Vec::<u8>::with_capacity(64);
+        while let Some(c) = self.cur() {
+            match c {
+                b'`' => {
+                    self.next();
+                    return String::from_utf8(result).ok();
+                }
+                b'\\' => result.extend(self.string_escape_sequence(false)?),
+                b'$' if self.cur_offset(1) == Some(b'{') => return None,
+                _ => {
+                    self.next();
+                    result.push(c);
                 }
             }
         }
@@ -556,20 +2661,376 @@ impl<'a> Parser<'a> {
         None
     }
 
+    fn check_export_statement(&mut self) {
+        // lexer.c throwIfExportStatement
+        let start_pos = self.pos;
+        self.next_offset(EXPORT.len());
+
+        if self.bracket_stack.is_empty() && self.cur().is_some() {
+            self.parse_result
+                .errors
+                .push(ParseError::UnexpectedExportStatement(ParseErrorMessage {
+                    pos: start_pos,
+                    message: String::from("Unexpected export statement in CJS module. "),
+                }));
+        }
+    }
+
+    /// Whether the `/` at `self.pos` starts a regex rather than a division, like lexer.c.
+    /// It is a regex when the last token is:
+    /// 1. A punctuator, except `)`, `]`, `}`, `++`, `--` and the `.` of `1.`. Like: call(firstParam, /regex/)
+    /// 2. The `)` of `if (...)`, `while (...)` or `for (...)`
+    /// 3. The `}` of a block or a class body, like `if (x) {}`, `=> {}`, `else {}`, but not `({})`
+    /// 4. A `/` that was a division
+    /// 5. A keyword that is followed by an expression, like `return`, `typeof`, `case` or `of`
+    /// 6. Nothing, at the start of the source
+    fn slash_is_regex(&self) -> bool {
+        let end = match self.last_token_pos {
+            Some(end) => end,
+            None => return true,
+        };
+        let closed_bracket = self.closed_bracket_at(end);
+        let before = end.checked_sub(1).map(|pos| self.source[pos]);
+
+        match self.source[end] {
+            b'.' if matches!(before, Some(b'0'..=b'9')) => false,
+            b'+' if before == Some(b'+') => false,
+            b'-' if before == Some(b'-') => false,
+            b')' => matches!(
+                closed_bracket,
+                Some(Bracket::Parenthesis(ParenthesisType::ParenthesisKeyword))
+            ),
+            b'}' => matches!(closed_bracket, Some(Bracket::Brace(BraceType::Block))),
+            b'/' => self.last_slash_was_division,
+            c if is_expression_punctuator(c) => true,
+            _ => is_expression_keyword(self.source, end),
+        }
+    }
+
+    /// The bracket closed by the `)`, `]` or `}` at `end`, if the main loop closed it
+    fn closed_bracket_at(&self, end: usize) -> Option<&Bracket> {
+        match &self.last_closed_bracket {
+            Some((pos, bracket)) if *pos == end => Some(bracket),
+            _ => None,
+        }
+    }
+
+    /// Whether the statement starting at `self.pos` is at top level and runs unconditionally,
+    /// judging by the brackets and the last token
+    fn is_top_level_statement(&self) -> bool {
+        if !self.bracket_stack.is_empty() {
+            return false;
+        }
+        let end = match self.last_token_pos {
+            Some(end) => end,
+            None => return true,
+        };
+        let before = end.checked_sub(1).map(|pos| self.source[pos]);
+
+        match self.source[end] {
+            b')' => !matches!(
+                self.closed_bracket_at(end),
+                Some(Bracket::Parenthesis(ParenthesisType::ParenthesisKeyword))
+            ),
+            b'?' | b':' => false,
+            c @ (b'&' | b'|') => before != Some(c),
+            _ => !is_preceding_keyword(self.source, end, b"else"),
+        }
+    }
+
+    /// `if (process.env.NODE_ENV === 'production') ... else ...` with `ParserOptions::constants`,
+    /// starting at `(`. Returns false, consuming nothing, if the condition is not a known constant.
+    fn try_parse_constant_if(&mut self) -> bool {
+        let revert_pos = self.pos;
+        let errors_len = self.parse_result.errors.len();
+
+        self.next();
+        self.comment_whitespace();
+        let taken = self.constant_condition();
+        self.comment_whitespace();
+
+        let taken = match taken {
+            Some(taken) if self.cur() == Some(b')') => taken,
+            _ => {
+                self.parse_result.errors.truncate(errors_len);
+                self.pos = revert_pos;
+                return false;
+            }
+        };
+
+        // Closed like any `if (...)`, so that a `/` after it is a regex
+        self.bracket_stack
+            .push(Bracket::Parenthesis(ParenthesisType::ParenthesisKeyword));
+        self.pop_bracket_stack();
+        self.last_token_pos = Some(self.pos - 1);
+
+        self.branch_depth += 1;
+        self.parse_branch(taken);
+
+        let else_pos = self.pos;
+        self.comment_whitespace();
+        if self.at_word(b"else") {
+            self.next_offset(b"else".len());
+            self.last_token_pos = Some(self.pos - 1);
+            self.parse_branch(!taken);
+        } else {
+            self.pos = else_pos;
+        }
+        self.branch_depth -= 1;
+
+        true
+    }
+
+    /// `a === 'x'`, `'x' != a`, where `a` is in `ParserOptions::constants`.
+    /// Returns whether the condition is true.
+    fn constant_condition(&mut self) -> Option<bool> {
+        let left = self.constant_operand()?;
+        self.comment_whitespace();
+
+        let negated = if self
+            .eat_bytes(b"===")
+            .or_else(|| self.eat_bytes(b"=="))
+            .is_some()
+        {
+            false
+        } else if self
+            .eat_bytes(b"!==")
+            .or_else(|| self.eat_bytes(b"!="))
+            .is_some()
+        {
+            true
+        } else {
+            return None;
+        };
+
+        self.comment_whitespace();
+        let right = self.constant_operand()?;
+
+        let (expression, value) = match (left, right) {
+            (ConstantOperand::Expression(expression), ConstantOperand::String(value))
+            | (ConstantOperand::String(value), ConstantOperand::Expression(expression)) => {
+                (expression, value)
+            }
+            _ => return None,
+        };
+        let constant = self.options.constants.get(&expression)?;
+
+        Some((*constant == value) != negated)
+    }
+
+    /// A string literal, or a member expression like `process.env.NODE_ENV`, or `typeof` of it
+    fn constant_operand(&mut self) -> Option<ConstantOperand> {
+        match self.cur() {
+            Some(b'\'' | b'"') => return self.string_literal(false).map(ConstantOperand::String),
+            Some(b'`') => return self.no_substitution_template().map(ConstantOperand::String),
+            _ => {}
+        }
+
+        let mut expression = self.identifer()?;
+        if expression == "typeof" {
+            self.comment_whitespace();
+            expression.push(' ');
+            expression.push_str(&self.identifer()?);
+        }
+
+        loop {
+            let dot_pos = self.pos;
+            self.comment_whitespace();
+            if self.eat(b'.').is_none() {
+                self.pos = dot_pos;
+                return Some(ConstantOperand::Expression(expression));
+            }
+            self.comment_whitespace();
+            expression.push('.');
+            expression.push_str(&self.identifer()?);
+        }
+    }
+
+    /// The statement after `if (...)` or `else`. Nothing in it is recorded unless it is `taken`,
+    /// but errors are reported either way.
+    fn parse_branch(&mut self, taken: bool) {
+        let outer = (!taken).then(|| self.take_branch_state());
+
+        self.comment_whitespace();
+        self.parse_statement();
+
+        if let Some(outer) = outer {
+            self.restore_branch_state(outer);
+        }
+    }
+
+    /// A block, an `if` statement with its `else` branches, or any other statement
+    fn parse_statement(&mut self) {
+        let depth = self.bracket_stack.len();
+
+        if self.cur() == Some(b'{') {
+            // { ... }
+            self.parse_token(b'{');
+            loop {
+                self.comment_whitespace();
+                match self.cur() {
+                    Some(c) if self.bracket_stack.len() > depth => self.parse_token(c),
+                    _ => break,
+                }
+            }
+        } else if self.at_word(IF) && self.branch_depth < MAX_BRANCH_DEPTH {
+            self.parse_if_statement();
+        } else {
+            // Up to `;`, `else`, a line break where the statement cannot continue, or the end
+            // of a body like `while (x) { ... }`
+            let mut started = false;
+            // Whether a body at depth is open, and if it is the one of `do`
+            let mut open_body: Option<bool> = None;
+            loop {
+                let whitespace_start = self.pos;
+                self.comment_whitespace();
+                let line_break = self.source[whitespace_start..self.pos]
+                    .iter()
+                    .any(|c| matches!(c, b'\n' | b'\r'))
+                    || std::str::from_utf8(&self.source[whitespace_start..self.pos])
+                        .is_ok_and(|skipped| skipped.contains(['\u{2028}', '\u{2029}']));
+
+                let at_depth = self.bracket_stack.len() == depth;
+                let can_continue = self
+                    .last_token_pos
+                    .is_some_and(|end| is_expression_punctuator(self.source[end]));
+                if started && at_depth && line_break && !can_continue {
+                    break;
+                }
+
+                match self.cur() {
+                    None => break,
+                    Some(_) if self.bracket_stack.len() < depth => break,
+                    Some(b')' | b']' | b'}') if at_depth => break,
+                    Some(b';') if at_depth => {
+                        self.parse_token(b';');
+                        break;
+                    }
+                    Some(b'e') if at_depth && started && self.at_word(b"else") => break,
+                    Some(b'{') if at_depth => {
+                        open_body = self.last_token_pos.and_then(|end| {
+                            let after_do = is_preceding_keyword(self.source, end, b"do");
+                            (after_do
+                                || is_preceding_keyword(self.source, end, b"try")
+                                || is_expression_terminator(self.source, end))
+                            .then_some(after_do)
+                        });
+                        self.parse_token(b'{');
+                    }
+                    Some(b'}') if self.bracket_stack.len() == depth + 1 => {
+                        self.parse_token(b'}');
+                        if let Some(after_do) = open_body.take() {
+                            if !self.continues_after_body(after_do) {
+                                break;
+                            }
+                        }
+                    }
+                    Some(c) => self.parse_token(c),
+                }
+                started = true;
+            }
+        }
+    }
+
+    /// Whether the statement goes on after the `}` of a body, with `else`, `catch`, `finally`,
+    /// the `while` of `do { ... } while (x)`, or an operator after a function expression
+    fn continues_after_body(&mut self, after_do: bool) -> bool {
+        self.comment_whitespace();
+
+        [b"else" as &[u8], b"catch", b"finally"]
+            .iter()
+            .any(|word| self.at_word(word))
+            || (after_do && self.at_word(WHILE))
+            || matches!(
+                self.cur(),
+                Some(b',' | b'.' | b'?' | b'=' | b'&' | b'|' | b'(' | b'[')
+            )
+    }
+
+    /// `if (...) ... else ...` as the statement of a branch, where the `else` belongs to it.
+    /// `else if` chains are read in a loop, nested statements recurse.
+    fn parse_if_statement(&mut self) {
+        let depth = self.bracket_stack.len();
+
+        loop {
+            self.next_offset(IF.len());
+            self.last_token_pos = Some(self.pos - 1);
+            self.comment_whitespace();
+            if self.cur() != Some(b'(') {
+                return;
+            }
+
+            self.parse_token(b'(');
+            if self.bracket_stack.len() == depth {
+                // A constant condition, where `try_parse_constant_if` read the `else` as well
+                return;
+            }
+            loop {
+                self.comment_whitespace();
+                match self.cur() {
+                    Some(c) if self.bracket_stack.len() > depth => self.parse_token(c),
+                    _ => break,
+                }
+            }
+            if self.bracket_stack.len() < depth || self.is_end() {
+                return;
+            }
+
+            self.branch_depth += 1;
+            self.comment_whitespace();
+            self.parse_statement();
+            self.branch_depth -= 1;
+
+            let else_pos = self.pos;
+            self.comment_whitespace();
+            if !self.at_word(b"else") {
+                self.pos = else_pos;
+                return;
+            }
+            self.next_offset(b"else".len());
+            self.last_token_pos = Some(self.pos - 1);
+            self.comment_whitespace();
+
+            if !self.at_word(IF) {
+                self.branch_depth += 1;
+                self.parse_statement();
+                self.branch_depth -= 1;
+                return;
+            }
+        }
+    }
+
+    /// Starts an untaken branch from empty results and bindings, so that nothing it records
+    /// is kept, and nothing recorded before it is used or removed by it.
+    /// Errors stay where they are, as they are reported either way.
+    fn take_branch_state(&mut self) -> BranchState {
+        let mut parse_result = std::mem::take(&mut self.parse_result);
+        self.parse_result.errors = std::mem::take(&mut parse_result.errors);
+
+        BranchState {
+            parse_result,
+            export_aliases: std::mem::take(&mut self.export_aliases),
+            require_bindings: std::mem::take(&mut self.require_bindings),
+            esbuild_exports: std::mem::take(&mut self.esbuild_exports),
+        }
+    }
+
+    fn restore_branch_state(&mut self, mut outer: BranchState) {
+        outer.parse_result.errors = std::mem::take(&mut self.parse_result.errors);
+
+        self.parse_result = outer.parse_result;
+        self.export_aliases = outer.export_aliases;
+        self.require_bindings = outer.require_bindings;
+        self.esbuild_exports = outer.esbuild_exports;
+    }
+
     /// https://tc39.es/ecma262/#sec-literals-regular-expression-literals
     /// # Note
     /// It depends on the syntax context when we are encountered with a '/'.
     /// The basic rule is, if we are going to parse an expression, we consider '/' as
     /// start of a Regular Expression Literal. Otherwise, we consider it as a division punctuator.
     ///
-    /// Even if it is a syntatic problem, we can identify '/' by the following rules, based on bracket analysis:
-    /// 1. The last token is a punctatutor. Like: call(firstParam, /regex/)
-    /// 2. The last token is a keyword that follows an expression (lexer.c isExpressionKeyword)
-    /// 3. We are at start of a block
-    ///     1. while (...)
-    ///     2. for (...)
-    ///     3. if (...)
-    ///
+    /// See `slash_is_regex` for how the syntax context is found.
     fn regex_literal(&mut self) {
         if let Some(b'/') = self.cur() {
             let start_pos = self.pos;
@@ -581,7 +3042,7 @@ impl<'a> Parser<'a> {
             while let Some(c) = self.cur() {
                 self.next();
 
-                if is_br(c) {
+                if is_br(c) || non_ascii_br_len(&self.source[self.pos - 1..]).is_some() {
                     self.parse_result
                         .errors
                         .push(ParseError::UnterminatedRegExp(ParseErrorMessage {
@@ -626,7 +3087,6 @@ impl<'a> Parser<'a> {
 
         if is_middle && matches!(self.cur(), Some(b'`')) {
             self.next();
-            self.expect_expression = false;
             return;
         }
 
@@ -636,7 +3096,6 @@ impl<'a> Parser<'a> {
             match c {
                 b'`' => {
                     self.next();
-                    self.expect_expression = false;
                     return;
                 }
                 b'\\' => {
@@ -645,7 +3104,6 @@ impl<'a> Parser<'a> {
                 b'$' if self.cur_offset(1) == Some(b'{') => {
                     self.next_offset(2);
                     self.bracket_stack.push(Bracket::TemplateBrace);
-                    self.expect_expression = true;
                     return;
                 }
                 _ => self.next(),
@@ -656,6 +3114,16 @@ impl<'a> Parser<'a> {
     pub fn parse(&mut self) -> ParseResult {
         self.pos = 0;
 
+        if self.check_utf8 {
+            self.check_utf8();
+        }
+
+        // Offsets stay relative to the source including the BOM
+        if self.source.starts_with(UTF8_BOM) {
+            self.start_pos = UTF8_BOM.len();
+            self.pos = self.start_pos;
+        }
+
         if let (Some(b'#'), Some(b'!')) = (self.cur(), self.cur_offset(1)) {
             while let Some(c) = self.cur() {
                 self.next();
@@ -670,111 +3138,195 @@ impl<'a> Parser<'a> {
                 self.next();
                 continue;
             }
-
-            if self.open_token_depth == 0 {
-                match c {
-                    b'i' => {
-                        // TODO: handle import
-                    }
-                    b'r' => {
-                        // TODO: handle require
-                    }
-                    _ => {}
+            if c >= 0x80 {
+                if let Some(len) = non_ascii_ws_len(&self.source[self.pos..]) {
+                    self.next_offset(len);
+                    continue;
                 }
             }
 
-            match c {
-                b'e' if self.source[self.pos..].starts_with(EXPORTS) && self.keyword_start() => {
-                    // lexer.c 134
-                    // TODO: keywordStart(pos) ?
-                    self.try_parse_exports_dot_assign(false);
-                }
-                // b'e' if self.source[self.pos..].starts_with(EXPORT) => {
-                //     // TODO: throwIfExportStatement
-                // }
-                b'r' if self.source[self.pos..].starts_with(REQUIRE) && self.keyword_start() => {
-                    self.try_parse_require();
-                }
-                b'i' | b'w' | b'f' | b'c' | b'd' | b'e' | b'n' | b'r' | b't' | b'v' | b'y'
-                | b'a'
-                    if self.keyword_start() =>
-                {
-                    let maybe_keyword = match_keyword(&self.source[self.pos..]);
+            self.parse_token(c);
+        }
 
-                    if maybe_keyword.is_some() {
-                        if let MaybeKeyword::Expression(s) = maybe_keyword {
-                            self.next_offset(s);
-                            self.expect_expression = true;
-                        }
-                        if let MaybeKeyword::Parenthesis(s) = maybe_keyword {
-                            self.next_offset(s);
-                            self.parenthesis_type = ParenthesisType::ParenthesisKeyword;
-                            self.expect_expression = false;
-                        }
-                    } else {
-                        // Just like `_` case
-                        self.next();
-                        self.expect_expression = false;
-                    }
-                }
-                b'\'' | b'"' => {
-                    self.string_literal(true);
-                }
-                b'`' => self.template_literal(false),
-                b'm' if self.source[self.pos..].starts_with(MODULE) && self.keyword_start() => {
-                    self.try_parse_module_exports_dot_assign();
+        if self.options.locations {
+            let locations = self
+                .parse_result
+                .compute_locations(&LineIndex::from_bytes(self.source));
+            self.parse_result.locations = Some(locations);
+        }
+
+        self.parse_result.clone()
+    }
+
+    /// One token of the main loop at `c`, which is not whitespace
+    fn parse_token(&mut self, c: u8) {
+        let token_start = self.pos;
+
+        match c {
+            // Whole words only, so that aliases like `exportsObj` reach the last arm
+            b'e' if self.at_word(EXPORTS) && self.keyword_start() => {
+                // lexer.c 134
+                // TODO: keywordStart(pos) ?
+                self.try_parse_exports_dot_assign(false, self.pos);
+            }
+            b'e' if self.at_word(EXPORT) && self.keyword_start() => {
+                self.check_export_statement();
+            }
+            b'i' if self.at_word(IMPORT) && self.keyword_start() => {
+                self.check_import_statement();
+            }
+            b'r' if self.at_word(REQUIRE) && self.keyword_start() => {
+                let require_pos = self.pos;
+                if let Some(record) = self.try_parse_require() {
+                    self.try_backtrack_add_require_binding(require_pos, record);
                 }
-                b'/' => {
-                    if matches!(self.cur_offset(1), Some(b'*' | b'/')) {
-                        self.comment_whitespace();
-                    } else if self.expect_expression {
-                        self.regex_literal();
-                    } else {
-                        // Division
-                        self.next();
-                        self.expect_expression = true;
-                    }
+            }
+            b'd' if self.options.reconcile_exports
+                && self.at_word(DELETE)
+                && self.keyword_start() =>
+            {
+                self.try_parse_delete_export();
+            }
+            b'c' if self.at_word(CLASS) && self.keyword_start() => {
+                self.next_offset(CLASS.len());
+                self.next_brace_is_class = true;
+            }
+            b'\'' | b'"' => {
+                self.string_literal(true);
+            }
+            b'`' => self.template_literal(false),
+            b'm' if self.at_word(MODULE) && self.keyword_start() => {
+                self.try_parse_module_exports_dot_assign();
+            }
+            b'O' if self.at_word(OBJECT) && self.keyword_start() => {
+                self.try_parse_object_define_or_keys(self.bracket_stack.is_empty());
+            }
+            b'_' if (self.at_word(TS_EXPORT) || self.at_word(TS_EXPORT_STAR))
+                && (self.keyword_start() || self.cur_neg_offset(1) == Some(b'.')) =>
+            {
+                // `tslib_1.__exportStar(...)` is accepted as well
+                self.try_parse_export_star();
+            }
+            b'_' if self.at_word(SWC_EXPORT) && self.keyword_start() => {
+                self.try_parse_swc_export();
+            }
+            b'_' if self.keyword_start() || self.cur_neg_offset(1) == Some(b'.') => {
+                // `tslib_1.__importDefault(...)` is accepted as well
+                match INTEROP_HELPERS
+                    .iter()
+                    .find(|(helper, _)| self.at_word(helper))
+                {
+                    Some((helper, interop)) => self.try_parse_interop_require(helper, *interop),
+                    None if self.keyword_start() => self.try_parse_export_alias(),
+                    None => self.next(),
                 }
-                c @ (b'(' | b'[' | b'{') => {
-                    match c {
-                        b'(' => {
-                            self.bracket_stack
-                                .push(Bracket::Parenthesis(self.parenthesis_type.clone()));
-
-                            if matches!(self.parenthesis_type, ParenthesisType::ParenthesisKeyword)
-                            {
-                                self.parenthesis_type = ParenthesisType::Plain;
-                            }
-                        }
-                        b'[' => self.bracket_stack.push(Bracket::Bracket),
-                        b'{' => self.bracket_stack.push(Bracket::Brace),
-                        _ => unreachable!(),
-                    }
-                    self.expect_expression = true;
+            }
+            // https://tc39.es/ecma262/#sec-html-like-comments
+            b'<' if self.source[self.pos..].starts_with(b"<!--") => {
+                self.line_comment();
+                return;
+            }
+            b'-' if self.source[self.pos..].starts_with(b"-->") && self.at_line_start() => {
+                self.line_comment();
+                return;
+            }
+            b'/' if matches!(self.cur_offset(1), Some(b'*' | b'/')) => {
+                self.comment_whitespace();
+                return;
+            }
+            b'/' => {
+                if self.slash_is_regex() {
+                    self.regex_literal();
+                    self.last_slash_was_division = false;
+                } else {
                     self.next();
+                    self.last_slash_was_division = true;
                 }
-                b')' | b']' | b'}' => {
-                    let bracket = self.pop_bracket_stack();
-
-                    if let Some(Bracket::TemplateBrace) = bracket {
-                        self.template_literal(true);
-                    }
-                }
-                c if is_punctuator(c) => {
-                    if c != b'.' {
-                        self.expect_expression = true;
+            }
+            b'(' => {
+                let parenthesis_type = match self.last_token_pos {
+                    Some(end) if is_paren_keyword(self.source, end) => {
+                        ParenthesisType::ParenthesisKeyword
                     }
+                    _ => ParenthesisType::Plain,
+                };
+                let is_if = matches!(parenthesis_type, ParenthesisType::ParenthesisKeyword)
+                    && self
+                        .last_token_pos
+                        .is_some_and(|end| is_preceding_keyword(self.source, end, IF));
+
+                // Deeply nested branches are scanned as usual, so that the stack cannot overflow
+                if !is_if
+                    || self.options.constants.is_empty()
+                    || self.branch_depth >= MAX_BRANCH_DEPTH
+                    || !self.try_parse_constant_if()
+                {
+                    self.bracket_stack
+                        .push(Bracket::Parenthesis(parenthesis_type));
                     self.next();
                 }
-                _ => {
-                    self.expect_expression = false;
+            }
+            b'[' => {
+                self.bracket_stack.push(Bracket::Bracket);
+                self.next();
+            }
+            b'{' => {
+                // A block at the start of the source is a statement as well
+                let is_block = self.next_brace_is_class
+                    || self
+                        .last_token_pos
+                        .is_none_or(|end| is_expression_terminator(self.source, end));
+                self.next_brace_is_class = false;
+                self.bracket_stack.push(Bracket::Brace(if is_block {
+                    BraceType::Block
+                } else {
+                    BraceType::Plain
+                }));
+                self.next();
+            }
+            b')' | b']' | b'}' => {
+                let bracket = self.pop_bracket_stack();
 
-                    self.next();
+                if let Some(Bracket::TemplateBrace) = bracket {
+                    self.template_literal(true);
                 }
             }
+            _ if !self.export_aliases.is_empty() && self.keyword_start() => {
+                self.try_parse_export_alias()
+            }
+            _ => self.next(),
         }
 
-        self.parse_result.clone()
+        // lexer.c lastTokenPos, skipping the whitespace and comments a helper stopped after
+        let token_end = if self.pos == self.whitespace_end {
+            self.whitespace_start
+        } else {
+            self.pos
+        };
+        if token_end > token_start {
+            self.last_token_pos = Some(token_end - 1);
+        }
+    }
+
+    /// Reports every invalid UTF-8 sequence in the source
+    fn check_utf8(&mut self) {
+        let mut pos = 0;
+
+        while let Err(error) = std::str::from_utf8(&self.source[pos..]) {
+            let invalid_pos = pos + error.valid_up_to();
+            self.parse_result
+                .errors
+                .push(ParseError::InvalidUtf8(ParseErrorMessage {
+                    pos: invalid_pos,
+                    message: String::from("Invalid UTF-8 sequence. "),
+                }));
+
+            match error.error_len() {
+                Some(len) => pos = invalid_pos + len,
+                // Cut off at the end
+                None => break,
+            }
+        }
     }
 
     pub fn print_current_line(&self) {
@@ -793,13 +3345,127 @@ impl<'a> Parser<'a> {
         );
     }
 
+    /// Span from `start` up to the current position
+    fn span_from(&self, start: usize) -> Span {
+        Span {
+            start,
+            end: self.pos,
+        }
+    }
+
+    fn identifier_with_span(&mut self) -> Option<(String, Span)> {
+        let start = self.pos;
+        let identifier = self.identifer()?;
+        Some((identifier, self.span_from(start)))
+    }
+
+    /// Identifier or string literal as an object property key
+    fn property_key(&mut self) -> Option<(String, Span)> {
+        let start = self.pos;
+        let key = match self.identifer() {
+            Some(key) => key,
+            None => self.string_literal(false)?,
+        };
+        Some((key, self.span_from(start)))
+    }
+
+    /// Consumes `c` if it is the current byte.
+    fn eat(&mut self, c: u8) -> Option<()> {
+        if self.cur() == Some(c) {
+            self.next();
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    /// Consumes `bytes` if the source continues with it.
+    fn eat_bytes(&mut self, bytes: &[u8]) -> Option<()> {
+        if self.source[self.pos..].starts_with(bytes) {
+            self.next_offset(bytes.len());
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    /// Consumes an identifier if it is exactly `name`.
+    fn eat_identifier(&mut self, name: &str) -> Option<()> {
+        if self.identifer()? == name {
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    /// Consumes a string literal if its value is exactly `value`.
+    fn eat_string(&mut self, value: &str) -> Option<()> {
+        if self.string_literal(false)? == value {
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    /// Whether `word` starts at the current position and is not merely the prefix of a longer identifier.
+    fn at_word(&self, word: &[u8]) -> bool {
+        self.source[self.pos..].starts_with(word)
+            && !matches!(
+                self.cur_offset(word.len()),
+                Some(c) if c >= 0x80 || c == b'\\' || is_identifier_char(char::from(c))
+            )
+    }
+
+    /// Consumes `word` if [`Parser::at_word`] matches.
+    fn eat_word(&mut self, word: &[u8]) -> Option<()> {
+        if self.at_word(word) {
+            self.next_offset(word.len());
+            Some(())
+        } else {
+            None
+        }
+    }
+
     fn keyword_start(&self) -> bool {
         match self.cur_neg_offset(1) {
             None => true,
+            Some(0x80..) => non_ascii_ws_len_back(&self.source[self.start_pos..self.pos]).is_some(),
             Some(c) => is_br_or_ws_or_puntuator_not_dot(c),
         }
     }
 
+    /// Whether only whitespace is between the last line terminator and `self.pos`, for
+    /// HTML-like `-->` comments. Block comments before `-->` on the same line are not supported.
+    fn at_line_start(&self) -> bool {
+        let mut pos = self.pos;
+
+        while pos > self.start_pos {
+            let before = &self.source[self.start_pos..pos];
+            match before[before.len() - 1] {
+                b'\r' | b'\n' => return true,
+                b'\t' | b'\x0b' | b'\x0c' | b' ' => pos -= 1,
+                _ => match non_ascii_ws_len_back(before) {
+                    Some(len) if non_ascii_br_len(&before[before.len() - len..]).is_some() => {
+                        return true
+                    }
+                    Some(len) => pos -= len,
+                    None => return false,
+                },
+            }
+        }
+
+        true
+    }
+
+    /// Opens the `(` of a call at `self.pos`. The `try_parse_*` helpers leave it open, so that the
+    /// main loop scans the rest of the arguments as usual, and closes it.
+    fn open_call_parenthesis(&mut self) {
+        self.bracket_stack
+            .push(Bracket::Parenthesis(ParenthesisType::Plain));
+        self.next();
+        self.comment_whitespace();
+    }
+
     fn pop_bracket_stack(&mut self) -> Option<Bracket> {
         if let Some(ch) = self.cur() {
             self.next();
@@ -820,17 +3486,7 @@ impl<'a> Parser<'a> {
                 }
 
                 // The bracket matches!
-
-                if matches!(
-                    old,
-                    Bracket::Parenthesis(ParenthesisType::ParenthesisKeyword)
-                ) {
-                    // End of place like: if (...)
-                    self.expect_expression = true;
-                } else {
-                    // End of place like: (a + b), { a: 1 }, [1, 2, 3]
-                    self.expect_expression = false;
-                }
+                self.last_closed_bracket = Some((self.pos - 1, old.clone()));
 
                 return Some(old);
             } else {
@@ -16,6 +16,62 @@ pub fn is_br(c: u8) -> bool {
     matches!(c, b'\r' | b'\n')
 }
 
+/// https://tc39.es/ecma262/#sec-white-space
+pub fn is_whitespace_char(ch: char) -> bool {
+    matches!(
+        ch,
+        '\t' | '\x0B' | '\x0C' | '\u{FEFF}'
+            // Category Zs
+            | ' '
+            | '\u{A0}'
+            | '\u{1680}'
+            | '\u{2000}'..='\u{200A}' | '\u{202F}' | '\u{205F}' | '\u{3000}'
+    )
+}
+
+/// https://tc39.es/ecma262/#sec-line-terminators
+pub fn is_line_terminator_char(ch: char) -> bool {
+    matches!(ch, '\n' | '\r' | '\u{2028}' | '\u{2029}')
+}
+
+/// Length of U+2028 or U+2029 at the start of `source`
+pub fn non_ascii_br_len(source: &[u8]) -> Option<usize> {
+    match source {
+        [0xE2, 0x80, 0xA8 | 0xA9, ..] => Some(3),
+        _ => None,
+    }
+}
+
+/// Length of the non-ASCII whitespace or line terminator at the start of `source`
+pub fn non_ascii_ws_len(source: &[u8]) -> Option<usize> {
+    let ch = first_non_ascii_char(source)?;
+    if is_whitespace_char(ch) || is_line_terminator_char(ch) {
+        Some(ch.len_utf8())
+    } else {
+        None
+    }
+}
+
+/// Length of the non-ASCII whitespace or line terminator at the end of `source`
+pub fn non_ascii_ws_len_back(source: &[u8]) -> Option<usize> {
+    // They are all 2 or 3 bytes long
+    (2..=3).find(|len| {
+        source.len() >= *len && non_ascii_ws_len(&source[source.len() - len..]) == Some(*len)
+    })
+}
+
+fn first_non_ascii_char(source: &[u8]) -> Option<char> {
+    if *source.first()? < 0x80 {
+        return None;
+    }
+    let end = std::cmp::min(source.len(), 4);
+    let valid = match std::str::from_utf8(&source[..end]) {
+        Ok(valid) => valid,
+        Err(error) => std::str::from_utf8(&source[..error.valid_up_to()]).ok()?,
+    };
+    valid.chars().next()
+}
+
 pub fn is_identifier_start(ch: char) -> bool {
     match ch {
         'a'..='z' => true,
@@ -35,29 +91,126 @@ pub fn is_identifier_char(ch: char) -> bool {
     }
 }
 
+/// Property name of a numeric literal key, like `"1"` for `{ 1.0: x }` and `"16"` for `{ 0x10: x }`,
+/// the way JavaScript converts numbers to strings
+pub fn numeric_property_name(literal: &str) -> Option<String> {
+    let digits = literal.replace('_', "");
+    let digits = digits.strip_suffix('n').unwrap_or(&digits);
+
+    let value = match digits.get(..2) {
+        Some("0x" | "0X") => radix_value(&digits[2..], 16)?,
+        Some("0o" | "0O") => radix_value(&digits[2..], 8)?,
+        Some("0b" | "0B") => radix_value(&digits[2..], 2)?,
+        // Annex B legacy octal, like `010`
+        _ if digits.len() > 1
+            && digits.starts_with('0')
+            && digits.bytes().all(|c| matches!(c, b'0'..=b'7')) =>
+        {
+            radix_value(digits, 8)?
+        }
+        _ if digits
+            .bytes()
+            .all(|c| matches!(c, b'0'..=b'9' | b'.' | b'e' | b'E')) =>
+        {
+            digits.parse::<f64>().ok()?
+        }
+        _ => return None,
+    };
+
+    let name = if value.is_infinite() {
+        String::from("Infinity")
+    } else if value != 0.0 && !(1e-6..1e21).contains(&value) {
+        // `1e+21`, `1e-7`
+        format!("{:e}", value)
+            .replacen("e", "e+", 1)
+            .replacen("e+-", "e-", 1)
+    } else {
+        format!("{}", value)
+    };
+    Some(name)
+}
+
+/// Value of integer digits in `radix`, accumulated as a `f64` like JavaScript once it does not
+/// fit in a `u128`
+fn radix_value(digits: &str, radix: u32) -> Option<f64> {
+    if let Ok(value) = u128::from_str_radix(digits, radix) {
+        return Some(value as f64);
+    }
+    if digits.is_empty() {
+        return None;
+    }
+    digits.chars().try_fold(0.0, |value: f64, c| {
+        Some(value * f64::from(radix) + f64::from(c.to_digit(radix)?))
+    })
+}
+
+pub static UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
 pub static REQUIRE: &[u8] = b"require";
 
 pub static EXPORTS: &[u8] = b"exports";
 
+pub static EXPORT: &[u8] = b"export";
+
+pub static IMPORT: &[u8] = b"import";
+
 pub static MODULE: &[u8] = b"module";
 
+pub static OBJECT: &[u8] = b"Object";
+
+pub static DEFINE_PROPERTY: &[u8] = b"defineProperty";
+
+pub static DEFINE_PROPERTIES: &[u8] = b"defineProperties";
+
+pub static ASSIGN: &[u8] = b"assign";
+
+pub static KEYS: &[u8] = b"keys";
+
+pub static TS_EXPORT: &[u8] = b"__export";
+
+pub static STAR: &[u8] = b"Star";
+
+pub static TS_EXPORT_STAR: &[u8] = b"__exportStar";
+
+pub static TO_COMMON_JS: &[u8] = b"__toCommonJS";
+
+pub static SWC_EXPORT: &[u8] = b"_export";
+
+pub static ES_MODULE: &str = "__esModule";
+
 pub static IF: &[u8] = b"if";
 
 pub static WHILE: &[u8] = b"while";
 
 pub static FOR: &[u8] = b"for";
 
+pub static VAR: &[u8] = b"var";
+
+pub static LET: &[u8] = b"let";
+
+pub static CONST: &[u8] = b"const";
+
+pub static CLASS: &[u8] = b"class";
+
+pub static DELETE: &[u8] = b"delete";
+
 #[derive(Clone)]
 pub enum ParenthesisType {
     ParenthesisKeyword, // if, while, for
     Plain,
 }
 
+#[derive(Clone)]
+pub enum BraceType {
+    Block, // after `)`, `;`, `=>`, `else`, `catch`, `finally`, and class bodies
+    Plain, // `{ a: 1, b: 2 }`
+}
+
 #[derive(Clone)]
 pub enum Bracket {
     Parenthesis(ParenthesisType), // '('
     Bracket,                      // '['
-    Brace,                        // '{' as in `function() {}`, `class {}`, `{ a: 1, b: 2 }`
+    Brace(BraceType),             // '{' as in `function() {}`, `class {}`, `{ a: 1, b: 2 }`
     TemplateBrace,                // '{' as '${' in template strings
 }
 
@@ -65,7 +218,7 @@ pub fn get_bracket_open_code(bracket: &Bracket) -> u8 {
     match bracket {
         Bracket::Parenthesis(_) => b'(',
         Bracket::Bracket => b'[',
-        Bracket::Brace => b'{',
+        Bracket::Brace(_) => b'{',
         Bracket::TemplateBrace => b'{',
     }
 }
@@ -74,11 +227,88 @@ pub fn get_bracket_close_code(bracket: &Bracket) -> u8 {
     match bracket {
         Bracket::Parenthesis(_) => b')',
         Bracket::Bracket => b']',
-        Bracket::Brace => b'}',
+        Bracket::Brace(_) => b'}',
         Bracket::TemplateBrace => b'}',
     }
 }
 
+/// lexer.c isExpressionPunctuator, `/` after them starts a regex.
+/// `)`, `]` and `}` depend on the bracket, `/` on whether it was a division.
+pub fn is_expression_punctuator(c: u8) -> bool {
+    matches!(
+        c,
+        b'!' | b'%'
+            | b'&'
+            | b'('
+            | b'*'..=b'.'
+            | b':'..=b'?'
+            | b'['
+            | b'^'
+            | b'{'
+            | b'|'
+            | b'~'
+    )
+}
+
+/// Whether `source[..=end]` ends with the keyword `word`, and not with an identifier like `xif`
+/// or a member like `x.if`
+pub fn is_preceding_keyword(source: &[u8], end: usize, word: &[u8]) -> bool {
+    if !source[..=end].ends_with(word) {
+        return false;
+    }
+    let before = &source[..end + 1 - word.len()];
+    match before.last() {
+        None => true,
+        Some(0x80..) => non_ascii_ws_len_back(before).is_some(),
+        Some(c) => is_br_or_ws_or_puntuator_not_dot(*c),
+    }
+}
+
+/// lexer.c isExpressionKeyword, for the last char of the token before `/`.
+/// `of` and `extends` are also followed by expressions.
+pub fn is_expression_keyword(source: &[u8], end: usize) -> bool {
+    static EXPRESSION_KEYWORDS: &[&[u8]] = &[
+        b"await",
+        b"case",
+        b"debugger",
+        b"delete",
+        b"do",
+        b"else",
+        b"extends",
+        b"in",
+        b"instanceof",
+        b"new",
+        b"of",
+        b"return",
+        b"throw",
+        b"typeof",
+        b"void",
+        b"yield",
+    ];
+    EXPRESSION_KEYWORDS
+        .iter()
+        .any(|word| is_preceding_keyword(source, end, word))
+}
+
+/// lexer.c isParenKeyword, for the last char of the token before `(`
+pub fn is_paren_keyword(source: &[u8], end: usize) -> bool {
+    [IF, WHILE, FOR]
+        .iter()
+        .any(|word| is_preceding_keyword(source, end, word))
+}
+
+/// lexer.c isExpressionTerminator, for the last char of the token before `{`.
+/// The brace opens a block, so a `/` after the closing brace starts a regex.
+pub fn is_expression_terminator(source: &[u8], end: usize) -> bool {
+    match source[end] {
+        b';' | b')' => true,
+        b'>' => end > 0 && source[end - 1] == b'=',
+        _ => [b"catch" as &[u8], b"finally", b"else"]
+            .iter()
+            .any(|word| is_preceding_keyword(source, end, word)),
+    }
+}
+
 pub enum MaybeKeyword {
     Expression(usize),
     Parenthesis(usize),
@@ -108,7 +338,8 @@ pub fn match_keyword(source: &[u8]) -> MaybeKeyword {
     let start_with_and_end = |offset: usize, token: &[u8]| {
         if source[offset..].starts_with(token)
             && (offset + token.len() >= source.len()
-                || is_br_or_ws_or_puntuator_not_dot(source[offset + token.len()]))
+                || is_br_or_ws_or_puntuator_not_dot(source[offset + token.len()])
+                || non_ascii_ws_len(&source[offset + token.len()..]).is_some())
         {
             return offset + token.len();
         }
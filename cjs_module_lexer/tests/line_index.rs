@@ -0,0 +1,63 @@
+#[cfg(test)]
+mod tests {
+    use cjs_module_lexer::line_index::*;
+    use cjs_module_lexer::parser::Span;
+
+    #[test]
+    fn test_line_terminators() {
+        let index = LineIndex::new("a\nb\r\nc\rd\u{2028}e\u{2029}f");
+
+        assert_eq!(index.line_count(), 6);
+        assert_eq!(index.line_col(2).line, 1);
+        assert_eq!(index.line_col(5).line, 2);
+        assert_eq!(index.line_col(7).line, 3);
+        assert_eq!(index.line_col(11).line, 4);
+        assert_eq!(index.line_col(15).line, 5);
+    }
+
+    #[test]
+    fn test_columns() {
+        let source = "x = '中😀';";
+        let index = LineIndex::new(source);
+        let end = source.len();
+
+        assert_eq!(
+            index.line_col(end),
+            LineCol {
+                line: 0,
+                column: end,
+                column_chars: 9,
+                column_utf16: 10,
+            }
+        );
+        // Inside '中' is moved back to its start, past the end is clamped
+        assert_eq!(index.line_col(6), index.line_col(5));
+        assert_eq!(index.line_col(end + 10), index.line_col(end));
+        assert_eq!(
+            index
+                .span_location(Span { start: 5, end: 8 })
+                .end
+                .column_utf16,
+            6
+        );
+    }
+
+    #[test]
+    fn test_invalid_utf8() {
+        // 0xE9 is Latin-1 `é`, 0xF0 0x9F is a cut off 4-byte char
+        let index = LineIndex::from_bytes(b"\xE9\xF0\x9F\nx = '\xE9\xF0\x9F';");
+
+        assert_eq!(
+            index.line_col(3),
+            LineCol {
+                line: 0,
+                column: 3,
+                column_chars: 2,
+                column_utf16: 2,
+            }
+        );
+        assert_eq!(index.line_col(12).column_chars, 7);
+        // Inside the cut off char is moved back to its start
+        assert_eq!(index.line_col(11), index.line_col(10));
+    }
+}
@@ -16,6 +16,12 @@ mod tests {
         let source = b"case.but not keyword";
         assert!(match_keyword(source).is_none());
 
+        let source = "case\u{A0}with unicode space".as_bytes();
+        assert!(match_keyword(source).is_some());
+
+        let source = "case\u{E0}".as_bytes();
+        assert!(match_keyword(source).is_none());
+
         let source = b"delete";
         assert!(match_keyword(source).is_some());
 
@@ -1,6 +1,8 @@
 #[cfg(test)]
 mod tests {
+    use cjs_module_lexer::line_index::*;
     use cjs_module_lexer::parser::*;
+    use std::collections::HashMap;
 
     #[test]
     fn test_shebang() {
@@ -25,13 +27,18 @@ mod tests {
         let mut p = Parser::new(source, "@");
         let r = p.parse();
 
+        // Spans are covered by test_spans
         assert_eq!(
-            r,
+            ParseResult {
+                export_records: vec![],
+                ..r
+            },
             ParseResult {
                 imports: vec![],
                 reexports: vec![],
                 exports: vec![String::from("u"), String::from("v")],
                 errors: vec![],
+                ..Default::default()
             }
         );
     }
@@ -47,17 +54,55 @@ mod tests {
         let mut p = Parser::new(source, "@");
         let r = p.parse();
 
+        // Spans are covered by test_spans
         assert_eq!(
-            r,
+            ParseResult {
+                export_records: vec![],
+                ..r
+            },
             ParseResult {
                 imports: vec![],
                 reexports: vec![],
                 exports: vec![String::from("u"), String::from("v"), String::from("中文")],
                 errors: vec![],
+                ..Default::default()
             }
         );
     }
 
+    #[test]
+    fn test_exports_dot_identifier_escape() {
+        let source = r#"
+            exports.\u0061 = 1;
+            exports.b\u{63} = 1;
+            exports.\u4E2D\u6587 = 1;
+            module.exports = { \u{1D4B3}: 1, e };
+            exports.f\u0020 = 1;
+            exports.\u0030 = 1;
+            exports.\u{110000} = 1;
+            exports.g = 1;
+        "#;
+
+        let mut p = Parser::new(source, "@");
+        let r = p.parse();
+
+        assert_eq!(r.exports, vec!["a", "bc", "中文", "𝒳", "e", "g"]);
+        assert_eq!(
+            r.errors
+                .iter()
+                .map(|error| match error {
+                    ParseError::UnexpectedUnicodeEscapeSequence(c, message) => (*c, message.pos),
+                    _ => panic!("{:?}", error),
+                })
+                .collect::<Vec<_>>(),
+            vec![
+                (' ', source.find("\\u0020").unwrap()),
+                ('0', source.find("\\u0030").unwrap()),
+                ('}', source.find("\\u{110000}").unwrap() + 9),
+            ]
+        );
+    }
+
     #[test]
     fn test_exports_dot_string_literal() {
         let source = r#"
@@ -67,13 +112,18 @@ mod tests {
         let mut p = Parser::new(source, "@");
         let r = p.parse();
 
+        // Spans are covered by test_spans
         assert_eq!(
-            r,
+            ParseResult {
+                export_records: vec![],
+                ..r
+            },
             ParseResult {
                 imports: vec![],
                 reexports: vec![],
                 exports: vec![String::from("u")],
                 errors: vec![],
+                ..Default::default()
             }
         );
     }
@@ -87,13 +137,18 @@ mod tests {
         let mut p = Parser::new(source, "@");
         let r = p.parse();
 
+        // Spans are covered by test_spans
         assert_eq!(
-            r,
+            ParseResult {
+                export_records: vec![],
+                ..r
+            },
             ParseResult {
                 imports: vec![],
                 reexports: vec![],
                 exports: vec![String::from("escape\\\t\r\x0B"),],
                 errors: vec![],
+                ..Default::default()
             }
         );
     }
@@ -111,8 +166,12 @@ mod tests {
         let mut p = Parser::new(source, "@");
         let r = p.parse();
 
+        // Spans are covered by test_spans
         assert_eq!(
-            r,
+            ParseResult {
+                export_records: vec![],
+                ..r
+            },
             ParseResult {
                 imports: vec![],
                 reexports: vec![],
@@ -124,8 +183,252 @@ mod tests {
                     String::from("漢字"),
                 ],
                 errors: vec![],
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_exports_dot_string_literal_surrogate() {
+        let source = r#"
+            exports['\ud83d\ude00'] = 1;
+            exports['\uD83D\u{DE00}'] = 1;
+            exports['\u{1F600}'] = 1;
+            exports['\u{00000000061}'] = 1;
+            exports['lone \ud83d'] = 1;
+            exports['lone \ude00\ud83d'] = 1;
+            exports['\u{110000}'] = 1;
+            exports['\u{FFFFFFFFFFFF}'] = 1;
+            var skipped = '\ud800-\udfff';
+            exports.b = 1;
+        "#;
+
+        let mut p = Parser::new(source, "@");
+        let r = p.parse();
+
+        assert_eq!(
+            r.exports,
+            vec![
+                "😀",
+                "😀",
+                "😀",
+                "a",
+                "lone \u{FFFD}",
+                "lone \u{FFFD}\u{FFFD}",
+                "b"
+            ]
+        );
+        assert_eq!(
+            r.errors
+                .iter()
+                .map(|error| match error {
+                    ParseError::LoneSurrogate(code_unit, message) => (*code_unit, message.pos),
+                    ParseError::UnexpectedUnicodeEscapeSequence(_, message) => (0, message.pos),
+                    _ => panic!("{:?}", error),
+                })
+                .collect::<Vec<_>>(),
+            vec![
+                (0xD83D, source.find("\\ud83d'").unwrap()),
+                (0xDE00, source.find("\\ude00\\ud83d").unwrap()),
+                (
+                    0xD83D,
+                    source
+                        .find("\\ud83d'] = 1;\n            exports['\\u{110000}")
+                        .unwrap()
+                ),
+                (0, source.find("\\u{110000}").unwrap() + 9),
+                (0, source.find("\\u{FFFFFFFFFFFF}").unwrap() + 15),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_no_panic() {
+        let sources = [
+            // `char::from_u32(...).unwrap()` on surrogates
+            r#"exports['\ud83d\ude00'] = 1;"#,
+            r#"var s = '\ud800';"#,
+            // `u32::from_str_radix(...).unwrap()` on overflow
+            r#"exports['\u{FFFFFFFFFFFFFFFFFFFF}'] = 1;"#,
+            // `next_offset(2)` past the end
+            r#"`\"#,
+            r#"import(`\"#,
+            r#"x = `${`\"#,
+            // `\` before a multi-byte char
+            r#"exports['\é'] = 1; `\é"#,
+            r#"exports.\u{1F600} = 1; exports.a\"#,
+            // Keywords before `/` at the start of the source
+            r#"do/x/; in/2"#,
+        ];
+
+        for source in sources {
+            // Every prefix, as inputs may end anywhere
+            for end in (0..=source.len()).filter(|end| source.is_char_boundary(*end)) {
+                let mut p = Parser::new(&source[..end], "@");
+                p.parse();
+                assert!(p.pos <= end);
+            }
+        }
+    }
+
+    #[test]
+    fn test_bytes() {
+        // Latin-1 `é` is 0xE9, which is not valid UTF-8
+        let source = b"exports.caf\xE9 = 1;\nexports['caf\xE9'] = 2;\nexports['\\u00E9\xE9'] = 3;\nexports.ok = 4;\xF0\x9F";
+
+        let mut p = Parser::from_bytes(source, "@");
+        let r = p.parse();
+
+        assert_eq!(
+            r.exports,
+            vec!["caf\u{FFFD}", "caf\u{FFFD}", "\u{E9}\u{FFFD}", "ok"]
+        );
+        assert_eq!(
+            r.errors,
+            vec![
+                ParseError::InvalidUtf8(ParseErrorMessage {
+                    pos: 11,
+                    message: String::from("Invalid UTF-8 sequence. "),
+                }),
+                ParseError::InvalidUtf8(ParseErrorMessage {
+                    pos: 30,
+                    message: String::from("Invalid UTF-8 sequence. "),
+                }),
+                ParseError::InvalidUtf8(ParseErrorMessage {
+                    pos: 54,
+                    message: String::from("Invalid UTF-8 sequence. "),
+                }),
+                ParseError::InvalidUtf8(ParseErrorMessage {
+                    pos: source.len() - 2,
+                    message: String::from("Invalid UTF-8 sequence. "),
+                }),
+            ]
+        );
+        assert!(r
+            .export_records
+            .iter()
+            .all(|record| record.raw_name.is_none()));
+
+        let mut p = Parser::from_bytes_with_options(
+            source,
+            "@",
+            ParserOptions {
+                raw_names: true,
+                locations: true,
+                ..Default::default()
+            },
+        );
+        let r = p.parse();
+
+        assert_eq!(
+            r.export_records
+                .iter()
+                .map(|record| record.raw_name.clone().unwrap())
+                .collect::<Vec<_>>(),
+            vec![
+                b"caf\xE9".to_vec(),
+                b"caf\xE9".to_vec(),
+                "\u{E9}".bytes().chain([0xE9]).collect(),
+                b"ok".to_vec(),
+            ]
+        );
+        assert_eq!(r.locations.unwrap().errors[1].column_chars, 12);
+    }
+
+    #[test]
+    fn test_bom() {
+        let source = "\u{FEFF}exports.a = 1;";
+
+        let mut p = Parser::new(source, "@");
+        let r = p.parse();
+
+        assert_eq!(r.exports, vec!["a"]);
+        assert_eq!(r.export_records[0].span, Span { start: 11, end: 12 });
+
+        let source = "\u{FEFF}#!/usr/bin/env node\nexports.b = 1;";
+
+        let mut p = Parser::new(source, "@");
+        let r = p.parse();
+
+        assert_eq!(r.exports, vec!["b"]);
+        assert_eq!(r.errors, vec![]);
+    }
+
+    #[test]
+    fn test_utf16() {
+        let source = "\u{FEFF}// 中文😀\nexports['é'] = require('./😀');\nexports.a = 1;\n)";
+        let mut utf16: Vec<u16> = source.encode_utf16().collect();
+        // A lone surrogate in a comment
+        utf16.insert(3, 0xD800);
+        let utf16_source = String::from_utf16_lossy(&utf16);
+
+        let r = parse_utf16(&utf16, "@", ParserOptions::default());
+
+        let utf16_offset = |text: &str| {
+            let byte_offset = utf16_source.find(text).unwrap();
+            utf16_source[..byte_offset].encode_utf16().count()
+        };
+
+        assert_eq!(r.exports, vec!["é", "a"]);
+        assert_eq!(r.imports, vec!["./😀"]);
+        assert_eq!(
+            r.export_records[0].span,
+            Span {
+                start: utf16_offset("'é'"),
+                end: utf16_offset("'é'") + 3,
             }
         );
+        assert_eq!(
+            r.import_records[0].span,
+            Span {
+                start: utf16_offset("'./😀'"),
+                end: utf16_offset("'./😀'") + 6,
+            }
+        );
+        assert_eq!(r.export_records[1].span.start, utf16_offset("a = 1"));
+        assert_eq!(r.errors[0].message().pos, utf16.len());
+    }
+
+    #[test]
+    fn test_unicode_whitespace() {
+        let source = "
+            x =\u{A0}/'/; exports.a = 1; // '
+            function f() { return\u{3000}/'/ }
+            exports.b = 1;
+            ;\u{2028}exports.c = 1;
+            \u{FEFF}\u{205F}exports.d = 1;
+            // exports.x = 1;\u{2029}exports.e = 1;
+            /*\u{2028}*/exports.f = 1;
+        ";
+
+        let mut p = Parser::new(source, "@");
+        let r = p.parse();
+
+        assert_eq!(r.exports, vec!["a", "b", "c", "d", "e", "f"]);
+        assert_eq!(r.errors, vec![]);
+
+        let mut p = Parser::new("x = /a\u{2028}/;", "@");
+        let r = p.parse();
+
+        assert!(matches!(r.errors[..], [ParseError::UnterminatedRegExp(_)]));
+    }
+
+    #[test]
+    fn test_html_like_comments() {
+        let source = r#"
+            <!-- exports.a = 1;
+            x = 1 <!-- exports.b = 1;
+            --> exports.c = 1;
+            /* */ exports.d = 1;
+              --> exports.e = 1;
+            while (x --> 0) exports.f = 1;
+        "#;
+
+        let mut p = Parser::new(source, "@");
+        let r = p.parse();
+
+        assert_eq!(r.exports, vec!["d", "f"]);
+        assert_eq!(r.errors, vec![]);
     }
 
     #[test]
@@ -142,10 +445,31 @@ mod tests {
         assert_eq!(r.errors.len(), 0,);
     }
 
+    #[test]
+    fn test_exports_dot_string_literal_legacy_octal() {
+        let source = r#"
+            exports['\0'] = 1;
+            exports['\08'] = 1;
+            exports['\7a'] = 1;
+            exports['\101\1011'] = 1;
+            exports['\377\400'] = 1;
+            exports['\8\9'] = 1;
+        "#;
+
+        let mut p = Parser::new(source, "@");
+        let r = p.parse();
+
+        assert_eq!(
+            r.exports,
+            vec!["\0", "\08", "\x07a", "AA1", "\u{FF}\x200", "89"]
+        );
+        assert_eq!(r.errors, vec![]);
+    }
+
     #[test]
     fn test_string_error() {
         let source = r#"
-            "invalid escape: \0"
+            "invalid escape: \u00G0"
         "#;
 
         let mut p = Parser::new(source, "@");
@@ -182,6 +506,435 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_module_dot_exports_values() {
+        let source = r#"
+            module.exports = {
+                a: function () { return /[}]/.test(x) },
+                b: require('./b'),
+                c: () => ({ x: 1 }),
+                d: f(x, { y }).z,
+                e: `${ { x } }`,
+                f(x) { return x / 2 },
+                get g() { return 1 },
+                set h(v) {},
+                async i() {},
+                async *j() {},
+                *k() {},
+                get() {},
+                async,
+                'l-m': 1,
+                1: 'n',
+                0x10: 'o',
+                1.50: 'o',
+                1e21: 'o',
+                010: 'o',
+                0xFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF: 'o',
+                [p]: 1,
+                ...require('./q'),
+                ...require('./r').s,
+                ...t,
+                u: x ? y : z,
+            };
+            exports.v = 1;
+        "#;
+
+        let mut p = Parser::new(source, "@");
+        let r = p.parse();
+
+        assert_eq!(r.errors, vec![]);
+        assert_eq!(
+            r.exports,
+            vec![
+                "a",
+                "b",
+                "c",
+                "d",
+                "e",
+                "f",
+                "g",
+                "h",
+                "i",
+                "j",
+                "k",
+                "get",
+                "async",
+                "l-m",
+                "1",
+                "16",
+                "1.5",
+                "1e+21",
+                "8",
+                "8.711228593176025e+40",
+                "u",
+                "v"
+            ]
+        );
+        assert_eq!(r.imports, vec!["./b", "./q", "./r"]);
+        assert_eq!(r.reexports, vec!["./q"]);
+
+        let options = ParserOptions {
+            raw_names: true,
+            ..Default::default()
+        };
+        let mut p = Parser::with_options("module.exports = { 0x10: a, 'b': b, c }", "@", options);
+        let r = p.parse();
+
+        assert_eq!(
+            r.export_records
+                .iter()
+                .map(|record| record.raw_name.clone().unwrap())
+                .collect::<Vec<_>>(),
+            vec![b"16".to_vec(), b"b".to_vec(), b"c".to_vec()]
+        );
+    }
+
+    #[test]
+    fn test_module_dot_exports_invalid() {
+        // Keys before the invalid property are kept, and brackets stay balanced
+        let source = r#"
+            module.exports = { a, b = 1, c };
+            module.exports = { d, 'e' };
+            exports.f = 1;
+        "#;
+
+        let mut p = Parser::new(source, "@");
+        let r = p.parse();
+
+        assert_eq!(r.errors, vec![]);
+        assert_eq!(r.exports, vec!["a", "d", "f"]);
+    }
+
+    #[test]
+    fn test_object_define_property() {
+        let source = r#"
+            Object.defineProperty(exports, "a", { enumerable: true, get: function () { return a; } });
+            Object.defineProperty(exports, 'b', { enumerable: true, get: function get() { return m.b; } });
+            Object.defineProperty(module.exports, "c", { enumerable: true, get() { return m["c"] } });
+            Object.defineProperty(exports, "d", { value: 1 });
+            Object.defineProperty(module.exports, "e", { enumerable: true, value: e });
+
+            // not exports
+            Object.defineProperty(exports, "f", { get: function () { return compute(); } });
+            Object.defineProperty(exports, "g", { enumerable: false, get: function () { return g; } });
+            Object.defineProperty(other, "h", { value: h });
+            Object.defineProperty(exportsLike, "i", { value: i });
+        "#;
+
+        let mut p = Parser::new(source, "@");
+        let r = p.parse();
+
+        assert_eq!(
+            r.exports,
+            vec![
+                String::from("a"),
+                String::from("b"),
+                String::from("c"),
+                String::from("d"),
+                String::from("e"),
+            ]
+        );
+        assert_eq!(r.errors, vec![]);
+    }
+
+    #[test]
+    fn test_typescript_export_star() {
+        let source = r#"
+            "use strict";
+            var __createBinding = (this && this.__createBinding) || (Object.create ? (function(o, m, k, k2) {
+                if (k2 === undefined) k2 = k;
+                var desc = Object.getOwnPropertyDescriptor(m, k);
+                if (!desc || ("get" in desc ? !m.__esModule : desc.writable || desc.configurable)) {
+                  desc = { enumerable: true, get: function() { return m[k]; } };
+                }
+                Object.defineProperty(o, k2, desc);
+            }) : (function(o, m, k, k2) {
+                if (k2 === undefined) k2 = k;
+                o[k2] = m[k];
+            }));
+            var __exportStar = (this && this.__exportStar) || function(m, exports) {
+                for (var p in m) if (p !== "default" && !Object.prototype.hasOwnProperty.call(exports, p)) __createBinding(exports, m, p);
+            };
+            function __export(m) {
+                for (var p in m) if (!exports.hasOwnProperty(p)) exports[p] = m[p];
+            }
+            Object.defineProperty(exports, "__esModule", { value: true });
+            __exportStar(require("./a"), exports);
+            tslib_1.__exportStar(require('./b'), exports);
+            __export(require("./c"));
+            __exportStar(require(dynamic), exports);
+        "#;
+
+        let mut p = Parser::new(source, "@");
+        let r = p.parse();
+
+        assert_eq!(
+            r.reexports,
+            vec![
+                String::from("./a"),
+                String::from("./b"),
+                String::from("./c")
+            ]
+        );
+        assert_eq!(
+            r.imports,
+            vec![
+                String::from("./a"),
+                String::from("./b"),
+                String::from("./c")
+            ]
+        );
+        assert_eq!(r.exports, Vec::<String>::new());
+        assert!(r.es_module);
+        assert_eq!(r.errors, vec![]);
+    }
+
+    #[test]
+    fn test_babel_reexport() {
+        let source = r#"
+            "use strict";
+
+            Object.defineProperty(exports, "__esModule", {
+              value: true
+            });
+            var _exportNames = {
+              named: true
+            };
+            var _a = require("./a");
+            let _b = require('./b');
+            const _c = require("./c");
+            var _d = require("./d");
+            var _notTopLevel = require("./not-top-level");
+
+            Object.keys(_a).forEach(function (key) {
+              if (key === "default" || key === "__esModule") return;
+              exports[key] = _a[key];
+            });
+
+            Object.keys(_b).forEach(function (key) {
+              if (key === "default" || key === "__esModule") return;
+              if (Object.prototype.hasOwnProperty.call(_exportNames, key)) return;
+              if (key in exports && exports[key] === _b[key]) return;
+              Object.defineProperty(exports, key, {
+                enumerable: true,
+                get: function () {
+                  return _b[key];
+                }
+              });
+            });
+
+            Object.keys(_c).forEach(function (k) {
+              if (k !== 'default' && !_exportNames.hasOwnProperty(k)) exports[k] = _c[k];
+            });
+
+            Object.keys(_d).forEach(function (key) {
+              if (key === "default" || key === "__esModule") return;
+              if (key in exports && exports[key] === _d[key]) return;
+              exports[key] = _d[key];
+            });
+
+            // unknown binding
+            Object.keys(_unknown).forEach(function (key) {
+              if (key === "default" || key === "__esModule") return;
+              exports[key] = _unknown[key];
+            });
+
+            function f() {
+              Object.keys(_notTopLevel).forEach(function (key) {
+                if (key === "default" || key === "__esModule") return;
+                exports[key] = _notTopLevel[key];
+              });
+            }
+        "#;
+
+        let mut p = Parser::new(source, "@");
+        let r = p.parse();
+
+        assert_eq!(
+            r.reexports,
+            vec![
+                String::from("./a"),
+                String::from("./b"),
+                String::from("./c"),
+                String::from("./d"),
+            ]
+        );
+        assert_eq!(r.errors, vec![]);
+    }
+
+    #[test]
+    fn test_esbuild() {
+        let source = r#"
+            "use strict";
+            var __defProp = Object.defineProperty;
+            var __export = (target, all) => {
+              for (var name in all)
+                __defProp(target, name, { get: all[name], enumerable: true });
+            };
+            var __toCommonJS = (mod) => __copyProps(__defProp({}, "__esModule", { value: true }), mod);
+
+            var inner_exports = {};
+            __export(inner_exports, {
+              notExported: () => notExported
+            });
+
+            var src_exports = {};
+            __export(src_exports, {
+              a: () => a,
+              "b-c": () => import_b.c,
+              default: () => src_default
+            });
+            module.exports = __toCommonJS(src_exports);
+            __reExport(src_exports, require("./d"), module.exports);
+
+            // Annotate the CommonJS export names for ESM import in node:
+            0 && (module.exports = {
+              a,
+              e,
+              ...require("./d")
+            });
+        "#;
+
+        let mut p = Parser::new(source, "@");
+        let r = p.parse();
+
+        assert_eq!(
+            r.exports,
+            vec![
+                String::from("a"),
+                String::from("b-c"),
+                String::from("default"),
+                String::from("e"),
+            ]
+        );
+        assert_eq!(r.reexports, vec![String::from("./d")]);
+        assert_eq!(r.errors, vec![]);
+
+        let source = r#"0&&(module.exports={a,b,...require("./c")});"#;
+
+        let mut p = Parser::new(source, "@");
+        let r = p.parse();
+
+        assert_eq!(r.exports, vec![String::from("a"), String::from("b")]);
+        assert_eq!(r.reexports, vec![String::from("./c")]);
+        assert_eq!(r.errors, vec![]);
+    }
+
+    #[test]
+    fn test_swc_export() {
+        let source = r#"
+            "use strict";
+            Object.defineProperty(exports, "__esModule", {
+                value: true
+            });
+            function _export(target, all) {
+                for(var name in all)Object.defineProperty(target, name, {
+                    enumerable: true,
+                    get: all[name]
+                });
+            }
+            _export(exports, {
+                foo: function() {
+                    return foo;
+                },
+                "bar-baz": function() {
+                    return _bar.default;
+                },
+                get: function() {
+                    return get;
+                },
+                get qux () {
+                    return _qux.qux;
+                }
+            });
+            _export(other, {
+                notExported: function() {
+                    return notExported;
+                }
+            });
+            var _bar = /*#__PURE__*/ _interop_require_default(require("./bar"));
+        "#;
+
+        let mut p = Parser::new(source, "@");
+        let r = p.parse();
+
+        assert_eq!(
+            r.exports,
+            vec![
+                String::from("foo"),
+                String::from("bar-baz"),
+                String::from("get"),
+                String::from("qux"),
+            ]
+        );
+        assert_eq!(r.imports, vec![String::from("./bar")]);
+        assert_eq!(r.errors, vec![]);
+    }
+
+    #[test]
+    fn test_es_module() {
+        let sources = [
+            r#"exports.__esModule = true;"#,
+            r#"module.exports.__esModule = !0;"#,
+            r#"exports["__esModule"] = true;"#,
+            r#"Object.defineProperty(exports, "__esModule", { value: true });"#,
+            r#"Object.defineProperty(exports,"__esModule",{value:!0});"#,
+            r#"module.exports = { __esModule: true };"#,
+            r#"Object.assign(exports, { __esModule: !0 });"#,
+        ];
+
+        for source in sources {
+            let mut p = Parser::new(source, "@");
+            let r = p.parse();
+
+            assert!(r.es_module, "{}", source);
+            assert_eq!(r.exports, Vec::<String>::new(), "{}", source);
+        }
+
+        let sources = [
+            r#"exports.__esModule = false;"#,
+            r#"Object.defineProperty(exports, "__esModule", { value: false });"#,
+            r#"exports.__esModule;"#,
+            r#"module.exports = { __esModule: false };"#,
+            r#"module.exports = { __esModule: true && x };"#,
+            r#"module.exports = { __esModule };"#,
+            r#"Object.assign(exports, { __esModule: false });"#,
+            r#"Object.defineProperty(exports, "__esModule", { get() { return x; } });"#,
+        ];
+
+        for source in sources {
+            let mut p = Parser::new(source, "@");
+            let r = p.parse();
+
+            assert!(!r.es_module, "{}", source);
+            assert_eq!(r.exports, Vec::<String>::new(), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_es_module_export() {
+        let source = r#"
+            Object.defineProperty(exports, "__esModule", { value: true });
+            exports.a = 1;
+        "#;
+
+        let mut p = Parser::with_options(
+            source,
+            "@",
+            ParserOptions {
+                es_module_export: true,
+                ..Default::default()
+            },
+        );
+        let r = p.parse();
+
+        assert!(r.es_module);
+        assert_eq!(
+            r.exports,
+            vec![String::from("__esModule"), String::from("a")]
+        );
+    }
+
     #[test]
     fn test_require() {
         let source = r#"
@@ -210,6 +963,265 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_require_interop() {
+        let source = r#"
+            var _a = _interopRequireDefault(require("a"));
+            var _b = _interopRequireWildcard(require("b"));
+            var _c = /*#__PURE__*/ _interop_require_default(require("c"));
+            var _d = _interop_require_wildcard(require("d"));
+            const e_1 = __importDefault(require("e"));
+            const f = tslib_1.__importStar(require('f'));
+            const g = require("g");
+            _interopRequireDefault(other);
+        "#;
+
+        let mut p = Parser::new(source, "@");
+        let r = p.parse();
+
+        assert_eq!(
+            r.import_records
+                .iter()
+                .map(|record| (record.specifier.as_str(), record.interop))
+                .collect::<Vec<_>>(),
+            vec![
+                ("a", Interop::Default),
+                ("b", Interop::Namespace),
+                ("c", Interop::Default),
+                ("d", Interop::Namespace),
+                ("e", Interop::Default),
+                ("f", Interop::Namespace),
+                ("g", Interop::Plain),
+            ]
+        );
+        assert_eq!(
+            r.imports,
+            r.import_records
+                .iter()
+                .map(|record| record.specifier.clone())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(r.errors, vec![]);
+    }
+
+    #[test]
+    fn test_babel_reexport_interop() {
+        let source = r#"
+            var _a = _interopRequireWildcard(require("./a"));
+            Object.keys(_a).forEach(function (key) {
+              if (key === "default" || key === "__esModule") return;
+              exports[key] = _a[key];
+            });
+        "#;
+
+        let mut p = Parser::new(source, "@");
+        let r = p.parse();
+
+        assert_eq!(r.reexports, vec![String::from("./a")]);
+    }
+
+    #[test]
+    fn test_esm_syntax() {
+        let sources = [
+            r#"import 'x';"#,
+            r#"import x from "x";"#,
+            r#"import{x}from"x";"#,
+            r#"import * as x from 'x';"#,
+            r#"export const x = 1;"#,
+            r#"export default x;"#,
+            r#"export{x};"#,
+            r#"console.log(import.meta.url);"#,
+        ];
+
+        for source in sources {
+            let mut p = Parser::new(source, "@");
+            let r = p.parse();
+
+            assert!(r.is_esm(), "{}", source);
+            assert_eq!(r.errors.len(), 1, "{}", source);
+        }
+
+        let source = r#"
+            import('x').then(m => m);
+            var imported = 1, exported = 2;
+            a.import('x');
+            b.export = 1;
+            const o = { import: 1, export: 2 };
+            exports.x = 1;
+        "#;
+
+        let mut p = Parser::new(source, "@");
+        let r = p.parse();
+
+        assert!(!r.is_esm());
+        assert_eq!(r.errors, vec![]);
+        assert_eq!(r.exports, vec![String::from("x")]);
+    }
+
+    #[test]
+    fn test_dynamic_import() {
+        let source = r#"
+            import('a');
+            const b = await import( "b" );
+            import(`c`).then(m => m);
+            import('d', { with: { type: 'json' } });
+            if (x) { import(/* comment */ 'e') }
+
+            // not collected
+            import(`f${g}`);
+            import('h' + i);
+            import(j);
+        "#;
+
+        let mut p = Parser::new(source, "@");
+        let r = p.parse();
+
+        let dynamic_import = |literal: &str| {
+            let start = source.find(literal).unwrap();
+            DynamicImport {
+                specifier: String::from(&literal[1..literal.len() - 1]),
+                span: Span {
+                    start,
+                    end: start + literal.len(),
+                },
+            }
+        };
+
+        assert_eq!(
+            r.dynamic_imports,
+            vec![
+                dynamic_import("'a'"),
+                dynamic_import("\"b\""),
+                dynamic_import("`c`"),
+                dynamic_import("'d'"),
+                dynamic_import("'e'"),
+            ]
+        );
+        assert_eq!(r.imports, Vec::<String>::new());
+        assert_eq!(r.errors, vec![]);
+    }
+
+    #[test]
+    fn test_spans() {
+        let source = r#"
+            exports.a = 1;
+            module.exports['b'] = 2;
+            Object.defineProperty(exports, 'c', { value: 3 });
+            module.exports = { d, e: 5, ...require('./f') };
+            __exportStar(require('./g'), exports);
+        "#;
+
+        let mut p = Parser::new(source, "@");
+        let r = p.parse();
+
+        let span_of = |text: &str| {
+            let start = source.find(text).unwrap();
+            Span {
+                start,
+                end: start + text.len(),
+            }
+        };
+        // `key` is searched for and may be followed by some context to make it unique
+        let export = |name: &str, key: &str, statement: &str| {
+            let start = source.find(key).unwrap();
+            let len = if key.starts_with('\'') {
+                name.len() + 2
+            } else {
+                name.len()
+            };
+            ExportRecord {
+                name: String::from(name),
+                span: Span {
+                    start,
+                    end: start + len,
+                },
+                statement_span: span_of(statement),
+                raw_name: None,
+            }
+        };
+
+        assert_eq!(
+            r.export_records,
+            vec![
+                export("a", "a =", "exports.a ="),
+                export("b", "'b'", "module.exports['b'] ="),
+                export("c", "'c'", "Object.defineProperty(exports, 'c', { value:"),
+                export(
+                    "d",
+                    "d, e",
+                    "module.exports = { d, e: 5, ...require('./f') }"
+                ),
+                export(
+                    "e",
+                    "e: 5",
+                    "module.exports = { d, e: 5, ...require('./f') }"
+                ),
+            ]
+        );
+        assert_eq!(
+            r.reexport_records,
+            vec![
+                ReexportRecord {
+                    specifier: String::from("./f"),
+                    span: span_of("'./f'"),
+                    statement_span: span_of("...require('./f')"),
+                },
+                ReexportRecord {
+                    specifier: String::from("./g"),
+                    span: span_of("'./g'"),
+                    statement_span: span_of("__exportStar(require('./g')"),
+                },
+            ]
+        );
+        assert_eq!(r.exports, vec!["a", "b", "c", "d", "e"]);
+        assert_eq!(r.reexports, vec!["./f", "./g"]);
+    }
+
+    #[test]
+    fn test_locations() {
+        let source = "// 中文\r\nexports.a = 1;\n  exports['😀'] = require('b');\n  );";
+
+        let mut p = Parser::with_options(
+            source,
+            "@",
+            ParserOptions {
+                locations: true,
+                ..Default::default()
+            },
+        );
+        let r = p.parse();
+        let locations = r.locations.unwrap();
+
+        let line_col = |line, column, column_chars, column_utf16| LineCol {
+            line,
+            column,
+            column_chars,
+            column_utf16,
+        };
+
+        assert_eq!(
+            locations.export_records[0].span,
+            SpanLocation {
+                start: line_col(1, 8, 8, 8),
+                end: line_col(1, 9, 9, 9),
+            }
+        );
+        // '😀' is 6 bytes, 3 chars and 4 UTF-16 code units
+        assert_eq!(
+            locations.export_records[1].span,
+            SpanLocation {
+                start: line_col(2, 10, 10, 10),
+                end: line_col(2, 16, 13, 14),
+            }
+        );
+        assert_eq!(
+            locations.import_records[0].statement_span.start,
+            line_col(2, 20, 17, 18)
+        );
+        assert_eq!(locations.errors.len(), r.errors.len());
+        assert_eq!(locations.errors[0].line, 3);
+    }
+
     #[test]
     fn test_react_dom() {
         let source = std::fs::read_to_string("tests/fixtures/react-dom.development.js").unwrap();
@@ -217,8 +1229,40 @@ mod tests {
         let mut p = Parser::new(source.as_str(), "react-dom.development.js");
         let r = p.parse();
 
+        let require_record = |specifier: &str| {
+            let statement = format!("require('{}')", specifier);
+            let start = source.find(&statement).unwrap();
+            ImportRecord {
+                specifier: String::from(specifier),
+                interop: Interop::Plain,
+                span: Span {
+                    start: start + "require(".len(),
+                    end: start + statement.len() - 1,
+                },
+                statement_span: Span {
+                    start,
+                    end: start + statement.len(),
+                },
+            }
+        };
+
         assert_eq!(
-            r,
+            r.import_records,
+            vec![require_record("react"), require_record("scheduler")]
+        );
+        assert_eq!(
+            r.exports,
+            r.export_records
+                .iter()
+                .map(|record| record.name.clone())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            ParseResult {
+                import_records: vec![],
+                export_records: vec![],
+                ..r
+            },
             ParseResult {
                 imports: vec![String::from("react"), String::from("scheduler")],
                 reexports: vec![],
@@ -237,10 +1281,335 @@ mod tests {
                     String::from("version"),
                 ],
                 errors: vec![],
+                import_records: vec![],
+                es_module: false,
+                dynamic_imports: vec![],
+                export_records: vec![],
+                reexport_records: vec![],
+                reexport_candidates: vec![],
+                locations: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_constants() {
+        let source = r#"
+            if (process.env.NODE_ENV === 'production') {
+                module.exports = require('./prod');
+            } else {
+                module.exports = require('./dev');
+            }
+            if (process.env.NODE_ENV !== "production") exports.dev = 1;
+            else exports.prod = 1
+            if (typeof window == 'undefined')
+                exports.server = require('./server')
+            else
+                exports.browser = require('./browser')
+            if ('development' === process.env.NODE_ENV) exports.a = 1; else if (x) exports.b = /[)]/;
+            if (process.env.OTHER === 'x') exports.c = 1; else exports.d = 1;
+            exports.e = 1;
+            if (process.env.NODE_ENV !== 'production') {
+                var _f = require('./f');
+            }
+            Object.keys(_f).forEach(function (key) {
+                if (key === "default" || key === "__esModule") return;
+                exports[key] = _f[key];
+            });
+        "#;
+
+        let options = ParserOptions {
+            constants: HashMap::from([
+                (
+                    String::from("process.env.NODE_ENV"),
+                    String::from("production"),
+                ),
+                (String::from("typeof window"), String::from("undefined")),
+            ]),
+            ..Default::default()
+        };
+        let mut p = Parser::with_options(source, "@", options.clone());
+        let r = p.parse();
+
+        assert_eq!(r.errors, vec![]);
+        assert_eq!(r.imports, vec!["./prod", "./server"]);
+        assert_eq!(r.reexports, vec!["./prod"]);
+        assert_eq!(r.reexport_candidates.len(), 1);
+        assert_eq!(r.exports, vec!["prod", "server", "b", "c", "d", "e"]);
+
+        // The untaken `else` is the whole `if (x) ... else ...` statement
+        let chain = r#"
+            if (process.env.NODE_ENV === 'production') {
+                module.exports = require('./prod');
+            } else if (x) {
+                module.exports = require('./a');
+            } else {
+                module.exports = require('./dev');
             }
+            if (process.env.NODE_ENV !== 'production') if (y) exports.a = 1; else exports.b = 1;
+            else exports.c = 1;
+            exports.d = 1;
+            if(process.env.NODE_ENV!=="production")while(x){x--}exports.e=1;
+            if(process.env.NODE_ENV!=="production")try{x()}catch(e){y()}finally{z()}exports.f=1;
+            if(process.env.NODE_ENV!=="production")do{x--}while(x);exports.g=1;
+            if("production"!==process.env.NODE_ENV)switch(x){case 1:y()}module.exports=require("./prod2");
+        "#;
+
+        let mut p = Parser::with_options(chain, "@", options);
+        let r = p.parse();
+
+        assert_eq!(r.errors, vec![]);
+        assert_eq!(r.imports, vec!["./prod", "./prod2"]);
+        assert_eq!(r.reexports, vec!["./prod2"]);
+        assert_eq!(r.exports, vec!["c", "d", "e", "f", "g"]);
+
+        // Without constants, every branch is recorded
+        let mut p = Parser::new(source, "@");
+        let r = p.parse();
+
+        assert_eq!(r.errors, vec![]);
+        assert_eq!(r.reexports, vec!["./dev", "./f"]);
+        assert_eq!(
+            r.exports,
+            vec!["dev", "prod", "server", "browser", "a", "b", "c", "d", "e"]
         );
     }
 
+    #[test]
+    fn test_reconcile_exports() {
+        let source = r#"
+            exports.a = 1;
+            __exportStar(require('./b'), exports);
+            module.exports = { c, d, ...require('./e') };
+            module.exports.f = 1;
+            exports.c = 2;
+            delete exports.d;
+            delete module.exports['f'];
+            exports.d = 3;
+            __exportStar(require('./e'), exports);
+            if (x) module.exports = {};
+            function g() { module.exports = other; }
+            var same = module.exports === exports;
+            module.exports == null;
+        "#;
+
+        let options = ParserOptions {
+            reconcile_exports: true,
+            ..Default::default()
+        };
+        let mut p = Parser::with_options(source, "@", options.clone());
+        let r = p.parse();
+
+        assert_eq!(r.errors, vec![]);
+        assert_eq!(r.exports, vec!["c", "d"]);
+        assert_eq!(
+            r.export_records
+                .iter()
+                .map(|record| record.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["c", "d"]
+        );
+        assert_eq!(r.reexports, vec!["./e"]);
+        assert_eq!(r.reexport_records.len(), 1);
+
+        // Append-only by default
+        let mut p = Parser::new(source, "@");
+        let r = p.parse();
+
+        assert_eq!(r.exports, vec!["a", "c", "d", "f", "c", "d"]);
+        assert_eq!(r.reexports, vec!["./b", "./e", "./e"]);
+
+        // `exports` is the previous object until it is assigned again
+        let source = r#"
+            exports.a = 1;
+            module.exports = function () {};
+            exports.b = 1;
+            Object.defineProperty(exports, 'c', { value: 1 });
+            module.exports.d = 1;
+            delete exports.d;
+        "#;
+
+        let mut p = Parser::with_options(source, "@", options.clone());
+        let r = p.parse();
+
+        assert_eq!(r.errors, vec![]);
+        assert_eq!(r.exports, vec!["d"]);
+
+        let source = r#"
+            module.exports = function () {};
+            module.exports = exports = { e };
+            exports.f = 1;
+        "#;
+
+        let mut p = Parser::with_options(source, "@", options);
+        let r = p.parse();
+
+        assert_eq!(r.exports, vec!["e", "f"]);
+    }
+
+    #[test]
+    fn test_export_aliases() {
+        let source = r#"
+            var e = module.exports;
+            e.a = 1;
+            e['b'] = 2;
+            const api = exports, other = 1;
+            api.c = 3;
+            other.no = 1;
+            module.exports = exports = { d };
+            exports.e = 1;
+            module.exports = x = y = { f };
+            x.g = 1;
+            y.h = 2;
+            function scoped() { var inner = exports; inner.no = 1; }
+            var notAlias = exports.a;
+            notAlias.no = 1;
+            ee.no = 1;
+            e.e.no = 1;
+            function shadowed(e) { e.no = 1; }
+            [1].forEach(function (e) { e.no = 1; });
+            var moduleExports = module.exports;
+            moduleExports.i = 1;
+            var exportsObj = exports;
+            exportsObj.j = 1;
+            var __exports = exports;
+            __exports.k = 1;
+            var o = exports;
+            o = {};
+            o.no = 1;
+            var p = exports;
+            p = module.exports;
+            p.l = 1;
+            var notAlias2 = exportsFoo;
+            notAlias2.no = 1;
+            var notAlias3 = module.exportsFoo;
+            notAlias3.no = 1;
+        "#;
+
+        let mut p = Parser::new(source, "@");
+        let r = p.parse();
+
+        assert_eq!(r.errors, vec![]);
+        assert_eq!(
+            r.exports,
+            vec!["a", "b", "c", "d", "e", "f", "g", "h", "i", "j", "k", "l"]
+        );
+
+        // Single char alias at the end
+        let mut p = Parser::new("var e = module.exports;\ne", "@");
+        let r = p.parse();
+
+        assert_eq!(r.exports, Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_object_assign_exports() {
+        let source = r#"
+            Object.assign(module.exports, { a, b: 1, ...require('./c') }, require('./d'), _e, { f() {} });
+            Object.assign(exports, require('./g').h, { no });
+            Object.defineProperties(exports, {
+                i: { enumerable: true, get() { return i; } },
+                __esModule: { value: true },
+                j: { value: 2 },
+                no: { enumerable: false, value: 1 },
+                no2: { enumerable: true, set(v) {} },
+                no3: { enumerable: true, get() { return compute(); } }
+            }, { no: 1 });
+            Object.assign(other, { no });
+            Object.assign(exports, f(), { no });
+            Object.assign(exports, { k: Object.assign(exports, { no }) }) / 2 / 1;
+            module.exports.l = Object.assign(exports, { m });
+        "#;
+
+        let mut p = Parser::new(source, "@");
+        let r = p.parse();
+
+        assert_eq!(r.errors, vec![]);
+        assert_eq!(r.exports, vec!["a", "b", "f", "i", "j", "k", "l", "m"]);
+        assert_eq!(r.reexports, vec!["./c", "./d"]);
+        assert_eq!(r.imports, vec!["./c", "./d", "./g"]);
+        assert!(r.es_module);
+
+        let mut p = Parser::new(
+            "Object.defineProperties(module.exports, { __esModule: { value: false } });",
+            "@",
+        );
+        let r = p.parse();
+
+        assert_eq!(r.exports, Vec::<String>::new());
+        assert!(!r.es_module);
+    }
+
+    #[test]
+    fn test_reexport_candidates() {
+        let source = r#"
+            module.exports = require('./a');
+            if (process.env.NODE_ENV === 'production') module.exports = require('./b');
+            else module.exports = require('./c');
+            if (prod) {
+                module.exports = require('./d');
+            }
+            prod ? (module.exports = require('./e')) : 0;
+            module.exports = require('./f');
+        "#;
+
+        let mut p = Parser::new(source, "@");
+        let r = p.parse();
+
+        assert_eq!(r.reexports, vec!["./f"]);
+        assert_eq!(
+            r.reexport_records
+                .iter()
+                .map(|record| record.specifier.as_str())
+                .collect::<Vec<_>>(),
+            vec!["./f"]
+        );
+        assert_eq!(
+            r.reexport_candidates
+                .iter()
+                .map(|candidate| (candidate.record.specifier.as_str(), candidate.top_level))
+                .collect::<Vec<_>>(),
+            vec![
+                ("./a", true),
+                ("./b", false),
+                ("./c", false),
+                ("./d", false),
+                ("./e", false),
+                ("./f", true),
+            ]
+        );
+
+        let source = std::fs::read_to_string("tests/fixtures/react-dom.js").unwrap();
+
+        let mut p = Parser::new(source.as_str(), "react-dom.js");
+        let r = p.parse();
+
+        assert_eq!(r.reexports, vec!["./cjs/react-dom.development.js"]);
+        assert_eq!(
+            r.reexport_candidates
+                .iter()
+                .map(|candidate| (candidate.record.specifier.as_str(), candidate.top_level))
+                .collect::<Vec<_>>(),
+            vec![
+                ("./cjs/react-dom.production.min.js", false),
+                ("./cjs/react-dom.development.js", false),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fixtures() {
+        for entry in std::fs::read_dir("tests/fixtures").unwrap() {
+            let path = entry.unwrap().path();
+            let source = std::fs::read_to_string(&path).unwrap();
+
+            let mut p = Parser::new(source.as_str(), "@");
+            let r = p.parse();
+
+            assert_eq!(r.errors, vec![], "{}", path.display());
+        }
+    }
+
     #[test]
     fn test_angular() {
         let source = std::fs::read_to_string("tests/fixtures/angular.js").unwrap();
@@ -249,6 +1618,7 @@ mod tests {
         p.parse();
 
         println!("{:?}", p.parse_result);
+        assert_eq!(p.parse_result.errors, vec![]);
     }
 
     #[test]
@@ -259,6 +1629,7 @@ mod tests {
         p.parse();
 
         println!("{:?}", p.parse_result);
+        assert_eq!(p.parse_result.errors, vec![]);
     }
 
     #[test]
@@ -269,6 +1640,7 @@ mod tests {
         p.parse();
 
         println!("{:?}", p.parse_result);
+        assert_eq!(p.parse_result.errors, vec![]);
     }
 
     #[test]
@@ -282,6 +1654,7 @@ mod tests {
         p.parse();
 
         println!("{:?}", p.parse_result);
+        assert_eq!(p.parse_result.errors, vec![]);
     }
 
     #[test]
@@ -292,6 +1665,7 @@ mod tests {
         p.parse();
 
         println!("{:?}", p.parse_result);
+        assert_eq!(p.parse_result.errors, vec![]);
     }
 
     #[test]
@@ -321,4 +1695,46 @@ mod tests {
 
         assert_eq!(r.errors, vec![])
     }
+
+    #[test]
+    fn test_regex_division() {
+        // A division read as a regex would swallow the export after it
+        let source = r#"
+            a++ / 2; exports.a = 1 / 2;
+            b-- / 2; exports.b = 1 / 2;
+            (x) / 2; exports.c = 1 / 2;
+            x = {} / 2; exports.d = 1 / 2;
+            1. / 2; exports.e = 1 / 2;
+            `${x}` / 2; exports.f = 1 / 2;
+            x.return / 2; exports.g = 1 / 2;
+            /re/g / 2; exports.h = 1 / 2;
+            require('x') / 2; exports.i = 1 / 2;
+        "#;
+
+        let mut p = Parser::new(source, "@");
+        let r = p.parse();
+
+        assert_eq!(r.errors, vec![]);
+        assert_eq!(r.exports, vec!["a", "b", "c", "d", "e", "f", "g", "h", "i"]);
+
+        // A regex read as a division would leave its brackets unbalanced
+        let source = r#"
+            if (x) {} /[)}]/.test(x)
+            x => {}
+            /[)}]/.test(x)
+            class A extends /[)}]/.constructor {} /[)}]/.test(x)
+            function f() { return /[)}]/ }
+            try {} catch {} finally {} /[)}]/.test(x)
+            do /[)}]/.test(x); while (0)
+            switch (typeof /[)}]/) { case /[)}]/: }
+            for (const x of /[)}]/.exec(y)) x = a ? /[)}]/ : /[)}]/
+            x = a / /[)}]/.lastIndex
+            void `${ {} }` + /[)}]/
+        "#;
+
+        let mut p = Parser::new(source, "@");
+        let r = p.parse();
+
+        assert_eq!(r.errors, vec![]);
+    }
 }
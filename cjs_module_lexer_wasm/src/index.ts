@@ -1,10 +1,85 @@
 import _init, { parse as _parse } from "../dist-wasm/cjs_module_lexer_wasm";
 import wasm from "../dist-wasm/cjs_module_lexer_wasm_bg.wasm";
 
+export type Interop = "Plain" | "Default" | "Namespace";
+
+export interface Span {
+  start: number;
+  end: number;
+}
+
+export interface ImportRecord {
+  specifier: string;
+  interop: Interop;
+  span: Span;
+  statement_span: Span;
+}
+
+export interface ExportRecord {
+  name: string;
+  span: Span;
+  statement_span: Span;
+  /** Always `null`, as `parse` takes a string and does not keep raw names */
+  raw_name: number[] | null;
+}
+
+export interface ReexportRecord {
+  specifier: string;
+  span: Span;
+  statement_span: Span;
+}
+
+/** Every `module.exports = require('x')`, of which only the last one is in `reexports` */
+export interface ReexportCandidate {
+  record: ReexportRecord;
+  /** `false` inside brackets or after `if (...)`, `else`, `?`, `:`, `&&`, `||` or `??` */
+  top_level: boolean;
+}
+
+export interface DynamicImport {
+  specifier: string;
+  span: Span;
+}
+
+export interface LineCol {
+  line: number;
+  /** In UTF-8 bytes */
+  column: number;
+  column_chars: number;
+  /** In UTF-16 code units, like JavaScript string indices */
+  column_utf16: number;
+}
+
+export interface SpanLocation {
+  start: LineCol;
+  end: LineCol;
+}
+
+export interface RecordLocation {
+  span: SpanLocation;
+  statement_span: SpanLocation;
+}
+
+export interface Locations {
+  errors: LineCol[];
+  import_records: RecordLocation[];
+  export_records: RecordLocation[];
+  reexport_records: RecordLocation[];
+  reexport_candidates: RecordLocation[];
+  dynamic_imports: SpanLocation[];
+}
+
 export interface ParseResult {
   imports: string[];
   exports: string[];
   reexports: string[];
+  import_records: ImportRecord[];
+  es_module: boolean;
+  dynamic_imports: DynamicImport[];
+  export_records: ExportRecord[];
+  reexport_records: ReexportRecord[];
+  reexport_candidates: ReexportCandidate[];
+  locations: Locations;
 }
 
 export async function init(): Promise<WebAssembly.Module> {
@@ -9,6 +9,11 @@ static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
 
 #[wasm_bindgen]
 pub fn parse(source: &str, name: &str) -> JsValue {
-    let mut p = Parser::new(source, name);
+    // JS consumers index strings in UTF-16 code units, see `LineCol::column_utf16`
+    let options = ParserOptions {
+        locations: true,
+        ..Default::default()
+    };
+    let mut p = Parser::with_options(source, name, options);
     return serde_wasm_bindgen::to_value(&p.parse()).unwrap();
 }